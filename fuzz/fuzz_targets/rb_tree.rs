@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rbtree::RbTree;
+
+fuzz_target!(|tree: RbTree<u16, u16>| {
+    assert!(tree.is_correct_rb_tree());
+    assert_eq!(tree.iter().count(), tree.len());
+    assert!(tree.pairs().all(|((a, _), (b, _))| a < b));
+});
@@ -1,7 +1,12 @@
 //! Crate of RB-Tree Implementation
 //!
-//! Provides sorted map feature which maintains its key order.
+//! Provides sorted map feature which maintains its key order, and sorted set feature built on it.
 
+mod rbset;
 mod rbtree;
 
-pub use self::rbtree::RbTree;
+pub use self::rbset::RbSet;
+pub use self::rbtree::{
+    Augment, Cursor, CursorMut, Entry, NoAugment, NodeRef, OccupiedEntry, RawEntryBuilderMut,
+    RawEntryMut, RawVacantEntryMut, RbTree, RepairReport, VacantEntry,
+};
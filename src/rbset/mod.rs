@@ -0,0 +1,422 @@
+use crate::rbtree::{self, RbTree};
+
+use std::{borrow::Borrow, cmp::Ordering, fmt::Debug, iter::Peekable, ops::RangeBounds};
+
+/// A sorted set implemented with RB-Tree.
+///
+/// It's a thin wrapper of `RbTree<T, ()>`.
+///
+/// # Examples
+///
+/// ```
+/// use rbtree::RbSet;
+///
+/// let mut books = RbSet::new();
+///
+/// // add some books.
+/// books.insert("A Dance With Dragons");
+/// books.insert("To Kill a Mockingbird");
+/// books.insert("The Odyssey");
+/// books.insert("The Great Gatsby");
+///
+/// // check for a specific one.
+/// if !books.contains("The Winds of Winter") {
+///     println!(
+///         "We have {} books, but The Winds of Winter ain't one.",
+///         books.len()
+///     );
+/// }
+///
+/// // remove a book.
+/// books.remove("The Odyssey");
+///
+/// // iterate over everything.
+/// for book in &books {
+///     println!("{book}");
+/// }
+///
+/// let a: RbSet<_> = [1, 2, 3].into_iter().collect();
+/// let b: RbSet<_> = [2, 3, 4].into_iter().collect();
+///
+/// assert!(a.union(&b).eq([1, 2, 3, 4].iter()));
+/// assert!(a.intersection(&b).eq([2, 3].iter()));
+/// assert!(a.difference(&b).eq([1].iter()));
+/// assert!(a.symmetric_difference(&b).eq([1, 4].iter()));
+/// assert!(!a.is_subset(&b) && !a.is_disjoint(&b));
+/// assert!(a.range(2..).eq([2, 3].iter()));
+/// ```
+pub struct RbSet<T> {
+    tree: RbTree<T, ()>,
+}
+
+impl<T> RbSet<T> {
+    /// Create new empty set
+    pub fn new() -> Self {
+        Self {
+            tree: RbTree::new(),
+        }
+    }
+
+    /// Adds a value to the set.
+    ///
+    /// Returns whether the value was newly inserted.
+    pub fn insert(&mut self, value: T) -> bool
+    where
+        T: Ord,
+    {
+        self.tree.insert(value, ()).is_none()
+    }
+
+    /// Check if the set contains the value.
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.contains_key(value)
+    }
+
+    /// Removes a value from the set.
+    ///
+    /// Returns whether the value was in the set.
+    pub fn remove<Q>(&mut self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.remove(value).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Makes the set empty.
+    pub fn clear(&mut self) {
+        self.tree.clear();
+    }
+
+    /// Returns an iterator over values in ascending order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            iter: self.tree.iter(),
+        }
+    }
+
+    /// Returns an iterator over values within `range` in ascending order.
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        Range {
+            range: self.tree.range(range),
+        }
+    }
+
+    /// Returns an iterator over values in `self` or `other` in ascending order, without duplicates.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbSet;
+    ///
+    /// let a: RbSet<_> = [1, 3, 5].into_iter().collect();
+    /// let b: RbSet<_> = [2, 3, 4].into_iter().collect();
+    ///
+    /// assert!(a.union(&b).eq([1, 2, 3, 4, 5].iter()));
+    /// assert!(a.union(&RbSet::new()).eq(a.iter()));
+    /// ```
+    pub fn union<'a>(&'a self, other: &'a RbSet<T>) -> Union<'a, T>
+    where
+        T: Ord,
+    {
+        Union {
+            left: self.iter().peekable(),
+            right: other.iter().peekable(),
+        }
+    }
+
+    /// Returns an iterator over values in both `self` and `other` in ascending order.
+    ///
+    /// Both sets are iterated together like `union`, instead of looking up each value in `other`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbSet;
+    ///
+    /// let a: RbSet<_> = (0..30).step_by(2).collect();
+    /// let b: RbSet<_> = (0..30).step_by(3).collect();
+    ///
+    /// assert!(a.intersection(&b).eq([0, 6, 12, 18, 24].iter()));
+    /// assert!(a.intersection(&b).eq(b.intersection(&a)));
+    /// assert_eq!(a.intersection(&RbSet::new()).count(), 0);
+    /// ```
+    pub fn intersection<'a>(&'a self, other: &'a RbSet<T>) -> Intersection<'a, T>
+    where
+        T: Ord,
+    {
+        Intersection {
+            left: self.iter(),
+            right: other.iter().peekable(),
+        }
+    }
+
+    /// Returns an iterator over values in `self` but not in `other` in ascending order.
+    ///
+    /// Like `intersection`, it advances through `other` alongside `self`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbSet;
+    ///
+    /// let a: RbSet<_> = (0..10).collect();
+    /// let b: RbSet<_> = [-1, 2, 3, 7, 20].into_iter().collect();
+    ///
+    /// assert!(a.difference(&b).eq([0, 1, 4, 5, 6, 8, 9].iter()));
+    /// assert!(b.difference(&a).eq([-1, 20].iter()));
+    /// assert!(a.difference(&RbSet::new()).eq(a.iter()));
+    /// ```
+    pub fn difference<'a>(&'a self, other: &'a RbSet<T>) -> Difference<'a, T>
+    where
+        T: Ord,
+    {
+        Difference {
+            left: self.iter(),
+            right: other.iter().peekable(),
+        }
+    }
+
+    /// Returns an iterator over values in exactly one of `self` and `other` in ascending order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbSet;
+    ///
+    /// let a: RbSet<_> = ["ant", "bee", "cat"].into_iter().collect();
+    /// let b: RbSet<_> = ["bee", "dog"].into_iter().collect();
+    ///
+    /// assert!(a.symmetric_difference(&b).eq(["ant", "cat", "dog"].iter()));
+    /// ```
+    pub fn symmetric_difference<'a>(&'a self, other: &'a RbSet<T>) -> impl Iterator<Item = &'a T>
+    where
+        T: Ord,
+    {
+        self.tree.symmetric_difference(&other.tree)
+    }
+
+    /// Returns `true` if every value of `self` is in `other`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbSet;
+    ///
+    /// let small: RbSet<_> = [2, 4].into_iter().collect();
+    /// let large: RbSet<_> = (1..=5).collect();
+    ///
+    /// assert!(small.is_subset(&large));
+    /// assert!(!large.is_subset(&small));
+    /// assert!(RbSet::new().is_subset(&small));
+    /// ```
+    pub fn is_subset(&self, other: &RbSet<T>) -> bool
+    where
+        T: Ord,
+    {
+        self.tree.is_subset(&other.tree)
+    }
+
+    /// Returns `true` if every value of `other` is in `self`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbSet;
+    ///
+    /// let large: RbSet<_> = (1..=5).collect();
+    ///
+    /// assert!(large.is_superset(&[1, 5].into_iter().collect()));
+    /// assert!(!large.is_superset(&[5, 6].into_iter().collect()));
+    /// ```
+    pub fn is_superset(&self, other: &RbSet<T>) -> bool
+    where
+        T: Ord,
+    {
+        self.tree.is_superset(&other.tree)
+    }
+
+    /// Returns `true` if `self` and `other` have no value in common.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbSet;
+    ///
+    /// let odds: RbSet<_> = (1..10).step_by(2).collect();
+    /// let evens: RbSet<_> = (0..10).step_by(2).collect();
+    ///
+    /// assert!(odds.is_disjoint(&evens));
+    /// assert!(!odds.is_disjoint(&[2, 3].into_iter().collect()));
+    /// ```
+    pub fn is_disjoint(&self, other: &RbSet<T>) -> bool
+    where
+        T: Ord,
+    {
+        self.tree.is_disjoint(&other.tree)
+    }
+}
+
+impl<T> Default for RbSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Debug for RbSet<T>
+where
+    T: Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T> FromIterator<T> for RbSet<T>
+where
+    T: Ord,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut set = RbSet::new();
+
+        for value in iter {
+            set.insert(value);
+        }
+
+        set
+    }
+}
+
+pub struct Iter<'a, T> {
+    iter: rbtree::Iter<'a, T, ()>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|(value, _)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+impl<'a, T> IntoIterator for &'a RbSet<T> {
+    type Item = &'a T;
+
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub struct Range<'a, T> {
+    range: rbtree::Range<'a, T, ()>,
+}
+
+impl<'a, T> Iterator for Range<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.range.next().map(|(value, _)| value)
+    }
+}
+
+pub struct Union<'a, T> {
+    left: Peekable<Iter<'a, T>>,
+    right: Peekable<Iter<'a, T>>,
+}
+
+impl<'a, T> Iterator for Union<'a, T>
+where
+    T: Ord,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let ordering = match (self.left.peek(), self.right.peek()) {
+            (Some(left), Some(right)) => left.cmp(right),
+            (Some(_), None) => Ordering::Less,
+            (None, _) => Ordering::Greater,
+        };
+
+        match ordering {
+            Ordering::Less => self.left.next(),
+            Ordering::Greater => self.right.next(),
+            Ordering::Equal => {
+                self.right.next();
+                self.left.next()
+            }
+        }
+    }
+}
+
+pub struct Intersection<'a, T> {
+    left: Iter<'a, T>,
+    right: Peekable<Iter<'a, T>>,
+}
+
+impl<'a, T> Iterator for Intersection<'a, T>
+where
+    T: Ord,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let left = self.left.next()?;
+
+            while self.right.next_if(|right| *right < left).is_some() {}
+
+            if self.right.next_if_eq(&left).is_some() {
+                return Some(left);
+            }
+        }
+    }
+}
+
+pub struct Difference<'a, T> {
+    left: Iter<'a, T>,
+    right: Peekable<Iter<'a, T>>,
+}
+
+impl<'a, T> Iterator for Difference<'a, T>
+where
+    T: Ord,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let left = self.left.next()?;
+
+            while self.right.next_if(|right| *right < left).is_some() {}
+
+            if self.right.peek() != Some(&left) {
+                return Some(left);
+            }
+        }
+    }
+}
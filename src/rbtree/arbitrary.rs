@@ -0,0 +1,46 @@
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use super::RbTree;
+
+/// Builds a tree by inserting an arbitrary sequence of key-value pairs.
+///
+/// # Examples
+///
+/// ```
+/// use arbitrary::{Arbitrary, Unstructured};
+/// use rbtree::RbTree;
+///
+/// let data: Vec<u8> = (0..=255).rev().collect();
+/// let mut u = Unstructured::new(&data);
+///
+/// let tree = RbTree::<u8, u16>::arbitrary(&mut u).unwrap();
+/// assert!(!tree.is_empty());
+/// assert!(tree.is_correct_rb_tree());
+/// ```
+impl<'a, K, V> Arbitrary<'a> for RbTree<K, V>
+where
+    K: Arbitrary<'a> + Ord,
+    V: Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut tree = RbTree::new();
+
+        for entry in u.arbitrary_iter::<(K, V)>()? {
+            let (key, value) = entry?;
+            tree.insert(key, value);
+        }
+
+        Ok(tree)
+    }
+
+    fn arbitrary_take_rest(u: Unstructured<'a>) -> Result<Self> {
+        let mut tree = RbTree::new();
+
+        for entry in u.arbitrary_take_rest_iter::<(K, V)>()? {
+            let (key, value) = entry?;
+            tree.insert(key, value);
+        }
+
+        Ok(tree)
+    }
+}
@@ -0,0 +1,210 @@
+use std::marker::PhantomData;
+
+use super::node::RbNode;
+
+/// Augmentation which keeps extra data in values computed from their subtrees,
+/// like subtree sums or the maximum end of intervals.
+///
+/// A tree created by `RbTree::with_augment` keeps it in its type, `RbTree<K, V, A>`, and calls `on_update`
+/// for every node whose entry or children changed after insertions and removals, and `on_rotate` inside rotations.
+/// Trees without augmentation use `NoAugment`, the default, which calls nothing.
+///
+/// # Examples
+///
+/// ```
+/// use rbtree::{Augment, NodeRef, RbTree};
+///
+/// struct Sum;
+///
+/// struct Value {
+///     value: i64,
+///     sum: i64,
+/// }
+///
+/// impl Augment<i32, Value> for Sum {
+///     fn on_update(_: &i32, value: &mut Value, left: Option<&Value>, right: Option<&Value>) {
+///         value.sum = value.value + left.map_or(0, |left| left.sum) + right.map_or(0, |right| right.sum);
+///     }
+/// }
+///
+/// // checks every subtree sum, and returns the sum of the subtree.
+/// fn check(node: Option<NodeRef<'_, i32, Value>>) -> i64 {
+///     match node {
+///         Some(node) => {
+///             let sum = node.value().value + check(node.left()) + check(node.right());
+///             assert_eq!(node.value().sum, sum);
+///             sum
+///         }
+///         None => 0,
+///     }
+/// }
+///
+/// let mut tree = RbTree::with_augment::<Sum>();
+/// for key in 0..100 {
+///     tree.insert(key, Value { value: key as i64, sum: 0 });
+/// }
+/// for key in (0..100).step_by(3) {
+///     tree.remove(&key);
+/// }
+/// tree.update(&1, |value| value.value = 1000);
+///
+/// let total: i64 = tree.iter().map(|(_, value)| value.value).sum();
+/// assert_eq!(check(tree.root_node()), total);
+///
+/// // sum of values with keys less than 50, descending once from the root.
+/// let mut prefix_sum = 0;
+/// let mut node = tree.root_node();
+/// while let Some(cur) = node {
+///     if *cur.key() < 50 {
+///         prefix_sum += cur.value().value + cur.left().map_or(0, |left| left.value().sum);
+///         node = cur.right();
+///     } else {
+///         node = cur.left();
+///     }
+/// }
+///
+/// let expected: i64 = tree.range(..50).map(|(_, value)| value.value).sum();
+/// assert_eq!(prefix_sum, expected);
+///
+/// // split trees keep the augmentation.
+/// let right = tree.split_off(&50);
+/// assert_eq!(check(tree.root_node()), prefix_sum);
+/// assert_eq!(check(right.root_node()), total - prefix_sum);
+/// ```
+///
+/// # Mutable access to values
+///
+/// The tree doesn't see writes through a mutable reference it handed out, so an augmented tree doesn't
+/// offer them: `get_mut`, `iter_mut`, `range_mut`, `IndexMut`, the `or_insert` methods of `Entry` and the like
+/// are only on trees without augmentation. Values of an augmented tree are changed by methods
+/// which pass `&mut V` to a closure, like `update`, `adjust_all`, `coalesce` and the `retain` methods,
+/// and the tree recomputes the augmented data after the closure returns, or unwinds.
+///
+/// ```compile_fail
+/// use rbtree::{Augment, RbTree};
+///
+/// struct Sum;
+///
+/// impl Augment<i32, (i64, i64)> for Sum {
+///     fn on_update(_: &i32, value: &mut (i64, i64), left: Option<&(i64, i64)>, right: Option<&(i64, i64)>) {
+///         value.1 = value.0 + left.map_or(0, |left| left.1) + right.map_or(0, |right| right.1);
+///     }
+/// }
+///
+/// let mut tree = RbTree::with_augment::<Sum>();
+/// tree.insert(0, (1, 0));
+///
+/// // the root wouldn't see this write.
+/// tree.get_mut(&0).unwrap().0 = 5;
+/// ```
+///
+/// ```
+/// use std::panic::{self, AssertUnwindSafe};
+/// use rbtree::{Augment, RbTree};
+///
+/// struct Sum;
+///
+/// // the value of the entry and the sum of its subtree.
+/// impl Augment<i32, (i64, i64)> for Sum {
+///     fn on_update(_: &i32, value: &mut (i64, i64), left: Option<&(i64, i64)>, right: Option<&(i64, i64)>) {
+///         value.1 = value.0 + left.map_or(0, |left| left.1) + right.map_or(0, |right| right.1);
+///     }
+/// }
+///
+/// let mut tree = RbTree::with_augment::<Sum>();
+/// for key in 0..10 {
+///     tree.insert(key, (1, 0));
+/// }
+///
+/// let total = |tree: &RbTree<i32, (i64, i64), Sum>| tree.root_node().unwrap().value().1;
+/// assert_eq!(total(&tree), 10);
+///
+/// tree.update(&0, |value| value.0 = 5);
+/// assert_eq!(total(&tree), 14);
+///
+/// tree.adjust_all(|_, value| value.0 *= 3);
+/// assert_eq!(total(&tree), 42);
+///
+/// // the value changed before the panic is recomputed too.
+/// let result = panic::catch_unwind(AssertUnwindSafe(|| {
+///     tree.retain(|key, value| {
+///         value.0 = 1;
+///         assert!(*key < 5);
+///         true
+///     });
+/// }));
+///
+/// assert!(result.is_err());
+/// assert_eq!(total(&tree), 6 + 4 * 3);
+/// ```
+pub trait Augment<K, V> {
+    /// Whether the tree calls the hooks at all.
+    ///
+    /// Only `NoAugment` turns it off, so trees without augmentation skip walking up to the root after changes.
+    const ENABLED: bool = true;
+
+    /// Recomputes the augmented data in `value` from its key and the values of its children.
+    fn on_update(key: &K, value: &mut V, left: Option<&V>, right: Option<&V>);
+
+    /// Recomputes the augmented data of a node whose children were changed by a rotation.
+    ///
+    /// It's called for both nodes of a rotation, the one moved down first, and defaults to `on_update`.
+    fn on_rotate(key: &K, value: &mut V, left: Option<&V>, right: Option<&V>) {
+        Self::on_update(key, value, left, right)
+    }
+}
+
+/// Augmentation of trees which keep no augmented data, the default of `RbTree`.
+///
+/// Only trees without augmentation hand out mutable references to their values.
+pub struct NoAugment;
+
+impl<K, V> Augment<K, V> for NoAugment {
+    const ENABLED: bool = false;
+
+    fn on_update(_: &K, _: &mut V, _: Option<&V>, _: Option<&V>) {}
+}
+
+/// Function pointer to a hook of `Augment`.
+pub(super) type AugmentFn<K, V> = fn(&K, &mut V, Option<&V>, Option<&V>);
+
+/// A read-only view of a node in a tree, for descending from the root like queries on augmented trees.
+///
+/// This is constructed from the `root_node` method on `RbTree`.
+pub struct NodeRef<'a, K, V> {
+    node: RbNode<K, V>,
+    _marker: PhantomData<(&'a K, &'a V)>,
+}
+
+impl<'a, K, V> NodeRef<'a, K, V> {
+    /// Returns the view of `node`, or `None` if it's Nil.
+    pub(super) fn new(node: RbNode<K, V>) -> Option<Self> {
+        match node.is_nil() {
+            true => None,
+            false => Some(NodeRef {
+                node,
+                _marker: PhantomData,
+            }),
+        }
+    }
+
+    /// Returns a reference to the key of this node.
+    pub fn key(&self) -> &'a K {
+        unsafe { self.node.key_value_ref().0 }
+    }
+
+    /// Returns a reference to the value of this node.
+    pub fn value(&self) -> &'a V {
+        unsafe { self.node.key_value_ref().1 }
+    }
+
+    /// Returns the left child, whose keys are all less than the key of this node.
+    pub fn left(&self) -> Option<Self> {
+        NodeRef::new(self.node.left.unwrap())
+    }
+
+    /// Returns the right child, whose keys are all greater than the key of this node.
+    pub fn right(&self) -> Option<Self> {
+        NodeRef::new(self.node.right.unwrap())
+    }
+}
@@ -0,0 +1,134 @@
+use super::{node::RbNode, Augment, NoAugment, RbTree};
+
+/// A cursor over a tree, pointing at an entry or at the "ghost" position past both ends.
+///
+/// Moving next from the ghost goes to the first entry, and moving previous from the ghost goes to the last entry.
+pub struct Cursor<'a, K, V, A: Augment<K, V> = NoAugment> {
+    pub(super) tree: &'a RbTree<K, V, A>,
+    pub(super) current: Option<RbNode<K, V>>,
+}
+
+impl<'a, K, V, A: Augment<K, V>> Cursor<'a, K, V, A> {
+    /// Returns a reference to the key of the current entry, or `None` at the ghost position.
+    pub fn key(&self) -> Option<&'a K> {
+        self.key_value().map(|(key, _)| key)
+    }
+
+    /// Returns a reference to the value of the current entry, or `None` at the ghost position.
+    pub fn value(&self) -> Option<&'a V> {
+        self.key_value().map(|(_, value)| value)
+    }
+
+    /// Returns references to the key-value pair of the current entry, or `None` at the ghost position.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// tree.insert(1, "a");
+    /// tree.insert(2, "b");
+    ///
+    /// let mut cursor = tree.cursor_at_index(0).unwrap();
+    /// assert_eq!(cursor.key_value(), Some((&1, &"a")));
+    ///
+    /// cursor.move_next();
+    /// assert_eq!(cursor.key_value(), Some((&2, &"b")));
+    ///
+    /// cursor.move_next();
+    /// assert_eq!(cursor.key_value(), None);
+    /// ```
+    pub fn key_value(&self) -> Option<(&'a K, &'a V)> {
+        let node = self.current?;
+
+        unsafe { Some(node.key_value_ref()) }
+    }
+
+    /// Moves the cursor to the next entry in key order.
+    pub fn move_next(&mut self) {
+        self.current = match self.current {
+            Some(node) => RbTree::next_node(node),
+            None if self.tree.is_empty() => None,
+            None => Some(RbTree::min_node(self.tree.root)),
+        };
+    }
+
+    /// Moves the cursor to the previous entry in key order.
+    pub fn move_prev(&mut self) {
+        self.current = match self.current {
+            Some(node) => RbTree::prev_node(node),
+            None if self.tree.is_empty() => None,
+            None => Some(RbTree::max_node(self.tree.root)),
+        };
+    }
+}
+
+/// A cursor over a tree with mutable access to values, pointing at an entry or at the "ghost" position
+/// past both ends.
+///
+/// This is constructed from the `into_cursor_mut` method on `OccupiedEntry`.
+pub struct CursorMut<'a, K, V, A: Augment<K, V> = NoAugment> {
+    pub(super) tree: &'a mut RbTree<K, V, A>,
+    pub(super) current: Option<RbNode<K, V>>,
+}
+
+impl<'a, K, V, A: Augment<K, V>> CursorMut<'a, K, V, A> {
+    /// Returns a reference to the key of the current entry, or `None` at the ghost position.
+    pub fn key(&self) -> Option<&K> {
+        self.key_value().map(|(key, _)| key)
+    }
+
+    /// Returns a reference to the value of the current entry, or `None` at the ghost position.
+    pub fn value(&self) -> Option<&V> {
+        self.key_value().map(|(_, value)| value)
+    }
+
+    /// Returns references to the key-value pair of the current entry, or `None` at the ghost position.
+    pub fn key_value(&self) -> Option<(&K, &V)> {
+        let node = self.current?;
+
+        unsafe { Some(node.key_value_ref()) }
+    }
+
+    /// Returns a read-only cursor at the current position, borrowing this cursor.
+    pub fn as_cursor(&self) -> Cursor<'_, K, V, A> {
+        Cursor {
+            tree: self.tree,
+            current: self.current,
+        }
+    }
+
+    /// Moves the cursor to the next entry in key order.
+    pub fn move_next(&mut self) {
+        self.current = match self.current {
+            Some(node) => RbTree::next_node(node),
+            None if self.tree.is_empty() => None,
+            None => Some(RbTree::min_node(self.tree.root)),
+        };
+    }
+
+    /// Moves the cursor to the previous entry in key order.
+    pub fn move_prev(&mut self) {
+        self.current = match self.current {
+            Some(node) => RbTree::prev_node(node),
+            None if self.tree.is_empty() => None,
+            None => Some(RbTree::max_node(self.tree.root)),
+        };
+    }
+}
+
+impl<K, V> CursorMut<'_, K, V> {
+    /// Returns a mutable reference to the value of the current entry, or `None` at the ghost position.
+    pub fn value_mut(&mut self) -> Option<&mut V> {
+        self.key_value_mut().map(|(_, value)| value)
+    }
+
+    /// Returns a reference to the key and a mutable reference to the value of the current entry,
+    /// or `None` at the ghost position.
+    pub fn key_value_mut(&mut self) -> Option<(&K, &mut V)> {
+        let node = self.current?;
+
+        unsafe { Some(node.key_value_mut()) }
+    }
+}
@@ -0,0 +1,447 @@
+use std::cmp::Ordering;
+
+use super::{node::RbNode, Augment, CursorMut, NoAugment, RbTree};
+
+/// A view into a single entry in a tree, which may either be vacant or occupied.
+///
+/// This is constructed from the `entry` method on `RbTree`.
+pub enum Entry<'a, K, V, A: Augment<K, V> = NoAugment> {
+    Vacant(VacantEntry<'a, K, V, A>),
+    Occupied(OccupiedEntry<'a, K, V, A>),
+}
+
+/// A view into a vacant entry in a tree.
+///
+/// It keeps the Nil node where the key should be inserted, so inserting doesn't search the tree again.
+pub struct VacantEntry<'a, K, V, A: Augment<K, V> = NoAugment> {
+    pub(super) tree: &'a mut RbTree<K, V, A>,
+    pub(super) node: RbNode<K, V>,
+    pub(super) key: K,
+}
+
+/// A view into an occupied entry in a tree.
+pub struct OccupiedEntry<'a, K, V, A: Augment<K, V> = NoAugment> {
+    pub(super) tree: &'a mut RbTree<K, V, A>,
+    pub(super) node: RbNode<K, V>,
+}
+
+impl<'a, K, V, A: Augment<K, V>> Entry<'a, K, V, A> {
+    /// Returns a reference to the key of this entry.
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Vacant(entry) => entry.key(),
+            Entry::Occupied(entry) => entry.key(),
+        }
+    }
+
+    /// Converts an occupied entry into a cursor at its entry, or returns `None` if the entry is vacant.
+    ///
+    /// It's the same as `into_cursor_mut` on `OccupiedEntry`.
+    pub fn cursor_mut(self) -> Option<CursorMut<'a, K, V, A>> {
+        match self {
+            Entry::Vacant(_) => None,
+            Entry::Occupied(entry) => Some(entry.into_cursor_mut()),
+        }
+    }
+}
+
+impl<'a, K, V> Entry<'a, K, V> {
+    /// Inserts `default` if the entry is vacant, and returns a mutable reference to the value.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Vacant(entry) => entry.insert(default),
+            Entry::Occupied(entry) => entry.into_mut(),
+        }
+    }
+
+    /// Inserts `default` if the entry is vacant, and returns a mutable reference to the value
+    /// with `true` if it was inserted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut counts = RbTree::new();
+    /// let mut distinct = 0;
+    ///
+    /// for word in ["a", "b", "a", "c", "b", "a"] {
+    ///     let (count, inserted) = counts.entry(word).or_insert_tracked(0);
+    ///     *count += 1;
+    ///
+    ///     if inserted {
+    ///         distinct += 1;
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(distinct, 3);
+    /// assert!(counts.iter().eq([(&"a", &3), (&"b", &2), (&"c", &1)]));
+    /// ```
+    pub fn or_insert_tracked(self, default: V) -> (&'a mut V, bool) {
+        match self {
+            Entry::Vacant(entry) => (entry.insert(default), true),
+            Entry::Occupied(entry) => (entry.into_mut(), false),
+        }
+    }
+
+    /// Inserts the result of `default` if the entry is vacant, and returns a mutable reference to the value.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Vacant(entry) => entry.insert(default()),
+            Entry::Occupied(entry) => entry.into_mut(),
+        }
+    }
+
+    /// Inserts the result of `default` if the entry is vacant and `default` succeeds,
+    /// and returns a mutable reference to the value.
+    ///
+    /// If `default` fails, nothing is inserted and the error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut config = RbTree::new();
+    ///
+    /// let value = config.entry("port").or_try_insert_with(|| "8080".parse::<u16>());
+    /// assert_eq!(value, Ok(&mut 8080));
+    ///
+    /// let value = config.entry("timeout").or_try_insert_with(|| "soon".parse::<u16>());
+    /// assert!(value.is_err());
+    /// assert!(!config.contains_key("timeout"));
+    /// assert_eq!(config.len(), 1);
+    /// ```
+    pub fn or_try_insert_with<E, F>(self, default: F) -> Result<&'a mut V, E>
+    where
+        F: FnOnce() -> Result<V, E>,
+    {
+        match self {
+            Entry::Vacant(entry) => Ok(entry.insert(default()?)),
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+        }
+    }
+
+    /// Inserts the result of `default` called with the key if the entry is vacant,
+    /// and returns a mutable reference to the value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    ///
+    /// let value = tree.entry("poneyland").or_insert_with_key(|key| key.len());
+    /// assert_eq!(*value, 9);
+    ///
+    /// // `default` is not called for the occupied entry.
+    /// let value = tree.entry("poneyland").or_insert_with_key(|_| unreachable!());
+    /// assert_eq!(*value, 9);
+    /// ```
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Vacant(entry) => {
+                let value = default(&entry.key);
+                entry.insert(value)
+            }
+            Entry::Occupied(entry) => entry.into_mut(),
+        }
+    }
+}
+
+impl<'a, K, V, A: Augment<K, V>> VacantEntry<'a, K, V, A> {
+    /// Returns a reference to the key which would be used when inserting.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Takes ownership of the key.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Inserts the value with the key of this entry, and returns the occupied entry of it.
+    ///
+    /// Unlike `insert`, it's offered by augmented trees too.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::{Entry, RbTree};
+    ///
+    /// let mut tree = RbTree::new();
+    /// tree.insert("a", 1);
+    ///
+    /// if let Entry::Vacant(entry) = tree.entry("b") {
+    ///     let entry = entry.insert_entry(2);
+    ///     assert_eq!((entry.key(), entry.get()), (&"b", &2));
+    /// }
+    ///
+    /// assert!(tree.iter().eq([(&"a", &1), (&"b", &2)]));
+    /// ```
+    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, K, V, A> {
+        let node = self.node;
+
+        self.tree.insert_at(node, self.key, value);
+
+        // rotations only relink nodes, so `node` still holds the inserted value.
+        OccupiedEntry {
+            tree: self.tree,
+            node,
+        }
+    }
+}
+
+impl<'a, K, V> VacantEntry<'a, K, V> {
+    /// Inserts the value with the key of this entry, and returns a mutable reference to the value.
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.insert_entry(value).into_mut()
+    }
+}
+
+impl<'a, K, V, A: Augment<K, V>> OccupiedEntry<'a, K, V, A> {
+    /// Returns a reference to the key of this entry.
+    pub fn key(&self) -> &K {
+        self.node.key()
+    }
+
+    /// Replaces the key of this entry with `key`, and returns the previous key.
+    ///
+    /// It's for refreshing parts of the key which are ignored by its order, like a timestamp of the last update.
+    /// `key` must compare equal to the previous key, or the key order of the tree breaks.
+    ///
+    /// # Panics
+    ///
+    /// With `debug_assertions`, panics if `key` doesn't compare equal to the previous key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::cmp::Ordering;
+    /// use rbtree::{Entry, RbTree};
+    ///
+    /// struct Session {
+    ///     id: u32,
+    ///     updated_at: u64,
+    /// }
+    ///
+    /// impl PartialEq for Session {
+    ///     fn eq(&self, other: &Self) -> bool {
+    ///         self.id == other.id
+    ///     }
+    /// }
+    ///
+    /// impl Eq for Session {}
+    ///
+    /// impl PartialOrd for Session {
+    ///     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    ///         Some(self.cmp(other))
+    ///     }
+    /// }
+    ///
+    /// impl Ord for Session {
+    ///     fn cmp(&self, other: &Self) -> Ordering {
+    ///         self.id.cmp(&other.id)
+    ///     }
+    /// }
+    ///
+    /// let mut sessions = RbTree::new();
+    /// for id in 0..3 {
+    ///     sessions.insert(Session { id, updated_at: 100 }, "data");
+    /// }
+    ///
+    /// if let Entry::Occupied(mut entry) = sessions.entry(Session { id: 1, updated_at: 0 }) {
+    ///     let old_key = entry.replace_key(Session { id: 1, updated_at: 250 });
+    ///     assert_eq!(old_key.updated_at, 100);
+    /// }
+    ///
+    /// let updated: Vec<_> = sessions.iter().map(|(session, _)| session.updated_at).collect();
+    /// assert_eq!(updated, [100, 250, 100]);
+    /// assert!(sessions.is_correct_rb_tree());
+    /// ```
+    pub fn replace_key(&mut self, key: K) -> K
+    where
+        K: Ord,
+    {
+        debug_assert!(
+            key.cmp(self.key()) == Ordering::Equal,
+            "replacing key must compare equal to the previous key"
+        );
+
+        let old_key = std::mem::replace(unsafe { self.node.key_mut() }, key);
+        self.tree.augment_path(Some(self.node));
+
+        old_key
+    }
+
+    /// Returns a reference to the value of this entry.
+    pub fn get(&self) -> &V {
+        self.node.value()
+    }
+
+    /// Converts the entry into a cursor at its entry, for walking to its neighbors without searching again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::{Entry, RbTree};
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in [10, 20, 30] {
+    ///     tree.insert(key, key);
+    /// }
+    ///
+    /// if let Entry::Occupied(entry) = tree.entry(20) {
+    ///     let mut cursor = entry.into_cursor_mut();
+    ///     assert_eq!(cursor.key(), Some(&20));
+    ///
+    ///     cursor.move_next();
+    ///     *cursor.value_mut().unwrap() += 5;
+    ///
+    ///     cursor.move_next();
+    ///     assert_eq!(cursor.key(), None);
+    /// }
+    ///
+    /// let mut cursor = tree.entry(10).cursor_mut().unwrap();
+    /// cursor.move_prev();
+    /// assert_eq!(cursor.key(), None);
+    /// cursor.move_prev();
+    /// assert_eq!(cursor.key_value(), Some((&30, &35)));
+    ///
+    /// assert!(tree.entry(15).cursor_mut().is_none());
+    /// assert!(tree.iter().eq([(&10, &10), (&20, &20), (&30, &35)]));
+    /// ```
+    pub fn into_cursor_mut(self) -> CursorMut<'a, K, V, A> {
+        CursorMut {
+            tree: self.tree,
+            current: Some(self.node),
+        }
+    }
+
+    /// Replaces the value of this entry and returns the previous value.
+    pub fn insert(&mut self, value: V) -> V {
+        let old_value = std::mem::replace(unsafe { self.node.value_mut() }, value);
+        self.tree.augment_path(Some(self.node));
+
+        old_value
+    }
+
+    /// Removes the entry from the tree and returns its value.
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Removes the entry from the tree and returns its key-value pair.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::{Entry, RbTree};
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 0..10 {
+    ///     tree.insert(key, key * 10);
+    /// }
+    ///
+    /// if let Entry::Occupied(entry) = tree.entry(4) {
+    ///     assert_eq!(entry.remove_entry(), (4, 40));
+    /// }
+    ///
+    /// assert!(!tree.contains_key(&4));
+    /// assert_eq!(tree.len(), 9);
+    /// assert!(tree.is_correct_rb_tree());
+    /// ```
+    pub fn remove_entry(self) -> (K, V) {
+        self.tree.remove_node(self.node).0
+    }
+}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V> {
+    /// Returns a mutable reference to the key of this entry.
+    ///
+    /// It's for updating parts of the key which are ignored by its order.
+    /// The updated key must compare equal to the previous key, or the key order of the tree breaks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::cmp::Ordering;
+    /// use rbtree::{Entry, RbTree};
+    ///
+    /// struct User {
+    ///     id: u32,
+    ///     name: &'static str,
+    /// }
+    ///
+    /// impl PartialEq for User {
+    ///     fn eq(&self, other: &Self) -> bool {
+    ///         self.id == other.id
+    ///     }
+    /// }
+    ///
+    /// impl Eq for User {}
+    ///
+    /// impl PartialOrd for User {
+    ///     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    ///         Some(self.cmp(other))
+    ///     }
+    /// }
+    ///
+    /// impl Ord for User {
+    ///     fn cmp(&self, other: &Self) -> Ordering {
+    ///         self.id.cmp(&other.id)
+    ///     }
+    /// }
+    ///
+    /// let mut tree = RbTree::new();
+    /// for (id, name) in [(1, "alice"), (2, "bob"), (3, "carol")] {
+    ///     tree.insert(User { id, name }, ());
+    /// }
+    ///
+    /// if let Entry::Occupied(mut entry) = tree.entry(User { id: 2, name: "" }) {
+    ///     entry.key_mut().name = "robert";
+    /// }
+    ///
+    /// let names: Vec<_> = tree.iter().map(|(user, _)| user.name).collect();
+    /// assert_eq!(names, ["alice", "robert", "carol"]);
+    /// assert!(tree.contains_key(&User { id: 2, name: "" }));
+    /// ```
+    pub fn key_mut(&mut self) -> &mut K {
+        unsafe { self.node.key_mut() }
+    }
+
+    /// Returns a mutable reference to the value of this entry.
+    pub fn get_mut(&mut self) -> &mut V {
+        unsafe { self.node.value_mut() }
+    }
+
+    /// Converts the entry into a mutable reference to its value with the lifetime of the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::{Entry, RbTree};
+    ///
+    /// let mut tree = RbTree::new();
+    /// tree.insert("a", 1);
+    ///
+    /// let value = {
+    ///     let entry = match tree.entry("a") {
+    ///         Entry::Occupied(entry) => entry,
+    ///         Entry::Vacant(_) => unreachable!(),
+    ///     };
+    ///
+    ///     entry.into_mut()
+    /// };
+    ///
+    /// // the entry is gone, but the reference is still usable.
+    /// *value += 10;
+    /// assert_eq!(tree["a"], 11);
+    /// ```
+    pub fn into_mut(self) -> &'a mut V {
+        let node = self.node;
+
+        unsafe { node.value_mut() }
+    }
+}
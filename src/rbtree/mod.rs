@@ -1,17 +1,35 @@
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+mod augment;
+mod cursor;
+mod entry;
 mod node;
+mod pool;
+mod raw_entry;
 
+pub use self::augment::{Augment, NoAugment, NodeRef};
+pub use self::cursor::{Cursor, CursorMut};
+pub use self::entry::{Entry, OccupiedEntry, VacantEntry};
+pub use self::raw_entry::{RawEntryBuilderMut, RawEntryMut, RawVacantEntryMut};
+
+use self::augment::AugmentFn;
 use self::node::{RbNode, RbNodeType};
+use self::pool::NodePool;
 
 use std::{
     borrow::Borrow,
+    cell::Cell,
+    cmp::Ordering,
+    collections::TryReserveError,
     fmt::Debug,
     marker::PhantomData,
-    ops::{Index, IndexMut},
+    ops::{Add, Bound, Index, IndexMut, RangeBounds},
 };
 
 /// A sorted map implemented with RB-Tree.
 ///
 /// It maintains RB-Tree attributes when inserting and removing nodes from tree.
+/// `A` is the augmentation of its values, `NoAugment` unless created by `with_augment`.
 ///
 /// # Examples
 ///
@@ -54,18 +72,451 @@ use std::{
 ///     println!("{movie}: \"{review}\"");
 /// }
 /// ```
-pub struct RbTree<K, V> {
+pub struct RbTree<K, V, A: Augment<K, V> = NoAugment> {
     root: RbNode<K, V>,
     len: usize,
+    max_len: Option<usize>,
+    lookup_cache: Option<Cell<Option<RbNode<K, V>>>>,
+    pool: NodePool<K, V>,
+    #[cfg(feature = "metrics")]
+    lookup_comparisons: Cell<usize>,
+    #[cfg(feature = "metrics")]
+    lookup_count: Cell<usize>,
+    #[cfg(feature = "metrics")]
+    rotation_count: u64,
+    _augment: PhantomData<A>,
 }
 
 impl<K, V> RbTree<K, V> {
     /// Create new empty RB-Tree
     pub fn new() -> Self {
+        Self::empty()
+    }
+
+    /// Create new empty RB-Tree which `checked_insert` keeps within `max_len` entries.
+    ///
+    /// Other insertions like `insert` are not limited.
+    pub fn with_max_len(max_len: usize) -> Self {
+        let mut tree = Self::new();
+        tree.max_len = Some(max_len);
+
+        tree
+    }
+
+    /// Create new empty RB-Tree which keeps the augmented data of its values with `A`.
+    ///
+    /// `A::on_update` is called for the nodes whose subtrees changed by insertions and removals,
+    /// and for the nodes whose values are replaced by `insert` or changed by closures like `update`.
+    /// `A::on_rotate` is called for the nodes of rotations. Methods handing out mutable references to values,
+    /// like `get_mut` and `iter_mut`, are not offered, since the augmented data wouldn't be recomputed.
+    /// Trees split off from it keep the augmentation.
+    ///
+    /// See `Augment` for an example.
+    pub fn with_augment<A: Augment<K, V>>() -> RbTree<K, V, A> {
+        RbTree::empty()
+    }
+
+    /// Create RB-Tree from key-value pairs in strictly ascending key order in O(n).
+    ///
+    /// The tree is built perfectly balanced, with the deepest level colored Red.
+    /// If the keys are not in strictly ascending order, the tree does not keep its key order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let tree = RbTree::from_sorted_iter((0..100).map(|key| (key, key * 2)));
+    ///
+    /// assert!(tree.is_correct_rb_tree());
+    /// assert_eq!(tree.len(), 100);
+    /// assert_eq!(tree[&42], 84);
+    /// ```
+    pub fn from_sorted_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut tree = RbTree::new();
+        tree.fill_sorted(iter);
+
+        tree
+    }
+
+    /// Create RB-Tree from key-value pairs in any order, like inserting them one by one.
+    ///
+    /// With `debug_assertions`, all RB-Tree invariants and the key order are checked after construction.
+    /// If the keys are not in a total order, like floats holding NaN, the tree may break.
+    ///
+    /// # Panics
+    ///
+    /// With `debug_assertions`, panics with the broken invariants if the built tree is not valid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// // keys in a scrambled order, with duplicates keeping the last value.
+    /// let tree = RbTree::from_iter_checked((0..20_000).map(|i| ((i * 7919) % 10_000, i)));
+    ///
+    /// assert_eq!(tree.len(), 10_000);
+    /// assert!(tree.is_correct_rb_tree());
+    /// assert!(tree.iter().map(|(key, _)| *key).eq(0..10_000));
+    /// assert_eq!(tree[&0], 10_000);
+    /// ```
+    pub fn from_iter_checked<I>(iter: I) -> Self
+    where
+        K: Ord,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut tree = RbTree::new();
+        for (key, value) in iter {
+            tree.insert(key, value);
+        }
+
+        if cfg!(debug_assertions) {
+            let (report, _) = tree.check_invariants();
+            assert!(
+                report.is_clean(),
+                "RB-Tree is broken after construction: {report:?}"
+            );
+        }
+
+        tree
+    }
+}
+
+impl<K, V, A: Augment<K, V>> RbTree<K, V, A> {
+    /// Returns a read-only view of the root node, or `None` if the tree is empty.
+    pub fn root_node(&self) -> Option<NodeRef<'_, K, V>> {
+        NodeRef::new(self.root)
+    }
+
+    /// Create new empty RB-Tree of the same augmentation, for `new` and trees split off from others.
+    fn empty() -> Self {
         Self {
             root: RbNode::new(None),
             len: 0,
+            max_len: None,
+            lookup_cache: None,
+            pool: NodePool::new(),
+            #[cfg(feature = "metrics")]
+            lookup_comparisons: Cell::new(0),
+            #[cfg(feature = "metrics")]
+            lookup_count: Cell::new(0),
+            #[cfg(feature = "metrics")]
+            rotation_count: 0,
+            _augment: PhantomData,
+        }
+    }
+
+    /// Recomputes the augmented data of `node` from its children.
+    fn augment_node(&self, node: RbNode<K, V>) {
+        Self::augment_node_with(node, A::on_update);
+    }
+
+    /// Recomputes the augmented data of `node` from its children with `hook`, one of the hooks of `A`.
+    fn augment_node_with(node: RbNode<K, V>, hook: AugmentFn<K, V>) {
+        if !A::ENABLED {
+            return;
+        }
+
+        let child_value = |child: RbNode<K, V>| match child.is_nil() {
+            true => None,
+            false => Some(unsafe { child.key_value_ref().1 }),
+        };
+
+        let (key, value) = unsafe { node.key_value_mut() };
+        hook(
+            key,
+            value,
+            child_value(node.left.unwrap()),
+            child_value(node.right.unwrap()),
+        );
+    }
+
+    /// Recomputes the augmented data from `node` up to the root.
+    fn augment_path(&self, mut node: Option<RbNode<K, V>>) {
+        if !A::ENABLED {
+            return;
+        }
+
+        while let Some(cur) = node {
+            self.augment_node(cur);
+            node = cur.parent;
+        }
+    }
+
+    /// Recomputes the augmented data of every node, children before parents.
+    fn augment_all(&self) {
+        if !A::ENABLED || self.root.is_nil() {
+            return;
+        }
+
+        // reversed pre-order visits every node after its children.
+        let mut nodes = Vec::with_capacity(self.len);
+        let mut stack = vec![self.root];
+
+        while let Some(node) = stack.pop() {
+            nodes.push(node);
+
+            for child in [node.left.unwrap(), node.right.unwrap()] {
+                if !child.is_nil() {
+                    stack.push(child);
+                }
+            }
+        }
+
+        for node in nodes.into_iter().rev() {
+            self.augment_node(node);
+        }
+    }
+
+    /// Returns the maximum number of entries configured by `with_max_len`.
+    pub fn max_len(&self) -> Option<usize> {
+        self.max_len
+    }
+
+    /// Enables or disables the one-entry cache of the last node found by key accessors,
+    /// like `get`, `get_mut`, `contains_key` or indexing.
+    ///
+    /// Those accessors check the cached node before descending from the root, which is faster
+    /// for workloads repeatedly looking up the same key. The cache is invalidated on any insertion or removal.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// tree.set_lookup_cache(true);
+    /// assert!(tree.lookup_cache_enabled());
+    ///
+    /// for key in 0..1000 {
+    ///     tree.insert(key, key * 2);
+    /// }
+    ///
+    /// // hot key: every lookup after the first hits the cache.
+    /// for _ in 0..10000 {
+    ///     assert_eq!(tree.get(&777), Some(&1554));
+    /// }
+    ///
+    /// // removal moves entries between nodes, but the cache never returns stale data.
+    /// tree.remove(&777);
+    /// assert_eq!(tree.get(&777), None);
+    /// assert!(!tree.contains_key(&777));
+    ///
+    /// assert_eq!(tree.get(&776), Some(&1552));
+    /// tree.remove(&776);
+    /// assert_eq!(tree.get(&778), Some(&1556));
+    /// assert_eq!(tree.get(&776), None);
+    /// ```
+    pub fn set_lookup_cache(&mut self, enabled: bool) {
+        self.lookup_cache = enabled.then(|| Cell::new(None));
+    }
+
+    /// Returns `true` if the lookup cache is enabled by `set_lookup_cache`.
+    pub fn lookup_cache_enabled(&self) -> bool {
+        self.lookup_cache.is_some()
+    }
+
+    /// Allocates nodes into the node pool, so that inserting `additional` more entries doesn't allocate nodes.
+    ///
+    /// Each entry takes two nodes from the pool, which are returned to it when the entry is removed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// tree.reserve_nodes(100);
+    /// assert_eq!(tree.pooled_nodes(), 200);
+    ///
+    /// for key in 0..100 {
+    ///     tree.insert(key, key);
+    /// }
+    /// assert_eq!(tree.pooled_nodes(), 0);
+    ///
+    /// // removed nodes are kept for the next insertions.
+    /// for key in 0..50 {
+    ///     tree.remove(&key);
+    /// }
+    /// assert_eq!(tree.pooled_nodes(), 100);
+    ///
+    /// tree.clear_pool();
+    /// assert_eq!(tree.pooled_nodes(), 0);
+    /// assert!(tree.iter().map(|(key, _)| *key).eq(50..100));
+    /// ```
+    #[cfg(feature = "pool")]
+    pub fn reserve_nodes(&mut self, additional: usize) {
+        self.pool.reserve(additional.saturating_mul(2));
+    }
+
+    /// Returns the number of freed nodes kept in the node pool for later insertions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// let mut peak = 0;
+    ///
+    /// // churn: nodes of removed entries are recycled, so the pool stops growing.
+    /// for round in 0..100 {
+    ///     for key in 0..1000 {
+    ///         tree.insert(key, round);
+    ///     }
+    ///     tree.retain(|key, _| key % 10 == 0);
+    ///
+    ///     if round == 0 {
+    ///         peak = tree.pooled_nodes();
+    ///     }
+    ///     assert_eq!(tree.pooled_nodes(), peak);
+    /// }
+    ///
+    /// assert_eq!(peak, 1800);
+    /// assert_eq!(tree.len(), 100);
+    /// assert!(tree.iter().all(|(key, round)| key % 10 == 0 && *round == 99));
+    /// assert!(tree.is_correct_rb_tree());
+    /// ```
+    #[cfg(feature = "pool")]
+    pub fn pooled_nodes(&self) -> usize {
+        self.pool.len()
+    }
+
+    /// Releases all freed nodes kept in the node pool.
+    #[cfg(feature = "pool")]
+    pub fn clear_pool(&mut self) {
+        self.pool.clear();
+    }
+
+    /// Forgets the cached node, which may be moved or freed.
+    fn invalidate_lookup_cache(&self) {
+        if let Some(cache) = &self.lookup_cache {
+            cache.set(None);
+        }
+    }
+
+    /// Build the empty tree from key-value pairs in strictly ascending key order.
+    fn fill_sorted<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut items: Vec<Option<(K, V)>> = iter.into_iter().map(Some).collect();
+
+        // depth of the deepest level, which is not full in general
+        let mut red_depth = 0;
+        while (1 << (red_depth + 1)) - 1 < items.len() {
+            red_depth += 1;
+        }
+
+        RbTree::build_subtree(self.root, &mut items, 0, red_depth, &mut self.pool);
+        self.len = items.len();
+        self.augment_all();
+    }
+
+    /// Takes all entries out to a new tree, leaving `self` empty with its configuration kept.
+    fn take_entries(&mut self) -> Self {
+        let mut empty = Self::empty();
+        empty.max_len = self.max_len;
+        empty.lookup_cache = self.lookup_cache.as_ref().map(|_| Cell::new(None));
+        std::mem::swap(&mut empty.pool, &mut self.pool);
+
+        #[cfg(feature = "metrics")]
+        {
+            empty.lookup_comparisons = self.lookup_comparisons.clone();
+            empty.lookup_count = self.lookup_count.clone();
+            empty.rotation_count = self.rotation_count;
+        }
+
+        std::mem::replace(self, empty)
+    }
+
+    /// Rebuilds the tree from its entries in key order.
+    ///
+    /// The entries are moved in one pass into a perfectly balanced tree,
+    /// whose height is the minimum for its length, log2(n + 1) rounded up.
+    ///
+    /// Since every entry moves to another node, raw pointers to keys or values
+    /// taken before the rebuild are dangling afterwards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 0..100 {
+    ///     tree.insert(key, key);
+    /// }
+    /// for key in (0..100).step_by(3) {
+    ///     tree.remove(&key);
+    /// }
+    ///
+    /// let before: Vec<_> = tree.iter().map(|(k, v)| (*k, *v)).collect();
+    /// tree.rebuild();
+    /// let after: Vec<_> = tree.iter().map(|(k, v)| (*k, *v)).collect();
+    ///
+    /// assert_eq!(before, after);
+    /// assert_eq!(tree.len(), before.len());
+    /// assert!(tree.is_correct_rb_tree());
+    ///
+    /// // 66 entries fit in 7 levels.
+    /// assert_eq!(tree.height(), 7);
+    /// ```
+    pub fn rebuild(&mut self) {
+        let entries = self.take_entries();
+        self.fill_sorted(entries);
+    }
+
+    /// Replaces every key with the result of `f`, and rebuilds the tree in O(n) keeping the key order.
+    ///
+    /// If `f` is strictly monotone, the mapped keys are already sorted and no sorting is needed.
+    /// Otherwise the entries are sorted by the mapped keys, and if some keys are mapped to the same key,
+    /// only the first of them in the previous key order is kept.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut events = RbTree::new();
+    /// for time in [30, 10, 20] {
+    ///     events.insert(time, time / 10);
+    /// }
+    ///
+    /// // shift time-keyed data by an offset.
+    /// events.remap_keys(|time| time + 100);
+    /// assert!(events.iter().eq([(&110, &1), (&120, &2), (&130, &3)]));
+    ///
+    /// // reverse the order.
+    /// events.remap_keys(|time| -time);
+    /// assert!(events.iter().eq([(&-130, &3), (&-120, &2), (&-110, &1)]));
+    ///
+    /// // squash some keys together.
+    /// events.remap_keys(|time| time / 20 * 20);
+    /// assert!(events.iter().eq([(&-120, &3), (&-100, &1)]));
+    /// assert!(events.is_correct_rb_tree());
+    /// ```
+    pub fn remap_keys<F>(&mut self, mut f: F)
+    where
+        K: Ord,
+        F: FnMut(K) -> K,
+    {
+        let mut entries: Vec<_> = self
+            .take_entries()
+            .into_iter()
+            .map(|(key, value)| (f(key), value))
+            .collect();
+
+        if !entries.windows(2).all(|pair| pair[0].0 < pair[1].0) {
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            entries.dedup_by(|(a, _), (b, _)| a == b);
         }
+
+        self.fill_sorted(entries);
     }
 
     /// Insert a node with key, value.
@@ -81,20 +532,139 @@ impl<K, V> RbTree<K, V> {
         if !cur.is_nil() {
             let old_value = unsafe { cur.value.assume_init_read() };
             cur.value.write(value);
+            self.augment_path(Some(cur));
 
             return Some(old_value);
         }
 
-        cur.init(key, value, RbNodeType::Red);
+        self.insert_at(cur, key, value);
+
+        None
+    }
+
+    /// Insert a node with key, value, replacing both the key and the value if there was a duplicate key.
+    ///
+    /// Unlike `insert`, which keeps the stored key, it returns the previous key-value pair on a duplicate key.
+    /// Otherwise returns `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::rc::Rc;
+    /// use rbtree::RbTree;
+    ///
+    /// let first: Rc<str> = Rc::from("key");
+    /// let second: Rc<str> = Rc::from("key");
+    ///
+    /// let mut tree = RbTree::new();
+    /// assert_eq!(tree.replace(Rc::clone(&first), 1), None);
+    ///
+    /// let (old_key, old_value) = tree.replace(Rc::clone(&second), 2).unwrap();
+    /// assert!(Rc::ptr_eq(&old_key, &first));
+    /// assert_eq!(old_value, 1);
+    ///
+    /// // the stored key is the new object.
+    /// let (stored_key, value) = tree.get_key_value("key").unwrap();
+    /// assert!(Rc::ptr_eq(stored_key, &second));
+    /// assert_eq!(*value, 2);
+    /// assert_eq!(tree.len(), 1);
+    /// ```
+    pub fn replace(&mut self, key: K, value: V) -> Option<(K, V)>
+    where
+        K: Ord,
+    {
+        let mut cur = self.find_nearest_node(&key);
+
+        if !cur.is_nil() {
+            let old_key_value =
+                unsafe { (cur.key.assume_init_read(), cur.value.assume_init_read()) };
+            cur.key.write(key);
+            cur.value.write(value);
+            self.augment_path(Some(cur));
+
+            return Some(old_key_value);
+        }
+
+        self.insert_at(cur, key, value);
+
+        None
+    }
+
+    /// Insert a node with key, value unless it makes the tree exceed `max_len`.
+    ///
+    /// Overwriting the value of an existing key is always allowed, and returns the previous value.
+    /// If the tree already has `max_len` entries, a new key-value pair is returned back as `Err`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut cache = RbTree::with_max_len(2);
+    ///
+    /// assert_eq!(cache.checked_insert(1, "a"), Ok(None));
+    /// assert_eq!(cache.checked_insert(2, "b"), Ok(None));
+    ///
+    /// // new key is rejected at the cap.
+    /// assert_eq!(cache.checked_insert(3, "c"), Err((3, "c")));
+    ///
+    /// // existing key is still overwritten.
+    /// assert_eq!(cache.checked_insert(2, "B"), Ok(Some("b")));
+    /// assert_eq!(cache.len(), 2);
+    /// ```
+    pub fn checked_insert(&mut self, key: K, value: V) -> Result<Option<V>, (K, V)>
+    where
+        K: Ord,
+    {
+        let mut cur = self.find_nearest_node(&key);
+
+        if !cur.is_nil() {
+            let old_value = unsafe { cur.value.assume_init_read() };
+            cur.value.write(value);
+            self.augment_path(Some(cur));
+
+            return Ok(Some(old_value));
+        }
+
+        if self.max_len.is_some_and(|max_len| self.len >= max_len) {
+            return Err((key, value));
+        }
+
+        self.insert_at(cur, key, value);
+
+        Ok(None)
+    }
+
+    /// Initialize Nil node `cur` with key, value and rebalance the tree.
+    ///
+    /// `cur` must be the Nil node returned from `find_nearest_node` with `key`.
+    fn insert_at(&mut self, mut cur: RbNode<K, V>, key: K, value: V) {
+        self.invalidate_lookup_cache();
+
+        cur.init(key, value, RbNodeType::Red, &mut self.pool);
         self.len += 1;
 
+        let mut ancestor = cur.parent;
+        while let Some(mut node) = ancestor {
+            node.size += 1;
+            ancestor = node.parent;
+        }
+
+        self.augment_path(Some(cur));
+        self.fix_double_red(cur);
+    }
+
+    /// Restores the RB-Tree attributes after Red node `cur` is linked, whose parent may also be Red.
+    ///
+    /// Returns `true` if the root was turned Black, which increases the black height of the tree by one.
+    fn fix_double_red(&mut self, mut cur: RbNode<K, V>) -> bool {
         // loop case 1 to 3: reassign colors
         loop {
             let (mut parent, mut grand_parent, mut uncle) = match cur.parent {
                 Some(parent) => {
                     // case 2: parent is Black
                     if parent.is_black() {
-                        return None;
+                        return false;
                     }
 
                     let grand_parent = parent.parent.unwrap();
@@ -111,7 +681,7 @@ impl<K, V> RbTree<K, V> {
                 // case 1: parent is None (cur is root)
                 None => {
                     cur.set_black();
-                    return None;
+                    return true;
                 }
             };
 
@@ -153,56 +723,447 @@ impl<K, V> RbTree<K, V> {
             self.rotate_left(grand_parent);
         }
 
-        None
+        false
     }
 
-    /// Removes a node by key and returns its value.
-    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    /// Gets the entry of the key for in-place manipulation.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, A>
     where
-        K: Borrow<Q>,
-        Q: Ord + ?Sized,
+        K: Ord,
     {
-        match self.remove_entry(key) {
-            None => None,
-            Some((_, value)) => Some(value),
+        let node = self.find_nearest_node(&key);
+
+        match node.is_nil() {
+            true => Entry::Vacant(VacantEntry {
+                tree: self,
+                node,
+                key,
+            }),
+            false => Entry::Occupied(OccupiedEntry { tree: self, node }),
         }
     }
 
-    /// Removes a node by key and returns its key-value pair.
-    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    /// Creates a raw entry builder, which looks up an entry by a key or a comparator closure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::{RawEntryMut, RbTree};
+    ///
+    /// let mut tree = RbTree::new();
+    /// tree.insert(String::from("apple"), 1);
+    /// tree.insert(String::from("banana"), 2);
+    ///
+    /// // search with a comparator closure, without building a `String`.
+    /// match tree.raw_entry_mut().from_key_with(|key| "banana".cmp(key.as_str())) {
+    ///     RawEntryMut::Occupied(mut entry) => *entry.get_mut() += 10,
+    ///     RawEntryMut::Vacant(_) => unreachable!(),
+    /// }
+    ///
+    /// assert_eq!(tree["banana"], 12);
+    /// ```
+    pub fn raw_entry_mut(&mut self) -> RawEntryBuilderMut<'_, K, V, A> {
+        RawEntryBuilderMut { tree: self }
+    }
+
+    /// Pushes `item` into the value of the key, inserting the default value first if the key is not in the tree.
+    ///
+    /// It searches the tree once, like `entry`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut groups: RbTree<i32, Vec<i32>> = RbTree::new();
+    /// for number in [5, 12, 7, 20, 15, 3] {
+    ///     groups.push_to_entry(number / 10, number);
+    /// }
+    ///
+    /// assert!(groups.iter().eq([(&0, &vec![5, 7, 3]), (&1, &vec![12, 15]), (&2, &vec![20])]));
+    /// ```
+    pub fn push_to_entry<T>(&mut self, key: K, item: T)
     where
-        K: Borrow<Q>,
-        Q: Ord + ?Sized,
+        K: Ord,
+        V: Default + Extend<T>,
     {
-        let mut target = self.find_nearest_node(key);
+        let cur = self.find_nearest_node(&key);
 
-        if target.is_nil() {
-            return None;
+        if cur.is_nil() {
+            self.insert_at(cur, key, V::default());
         }
 
-        let right_min = RbTree::min_node(target.right.unwrap());
-
-        let removed_key_value = unsafe {
-            (
-                target.key.assume_init_read(),
-                target.value.assume_init_read(),
-            )
+        // rotations only relink nodes, so `cur` holds the value of the key.
+        let _augment = AugmentOnDrop {
+            tree: self,
+            node: Some(cur),
         };
+        unsafe { cur.value_mut() }.extend(std::iter::once(item));
+    }
 
-        if !right_min.is_nil() {
-            unsafe {
-                target.key.write(right_min.key.assume_init_read());
-                target.value.write(right_min.value.assume_init_read());
+    /// Adds `by` to the value of the key, inserting `by` if the key is not in the tree, and returns the new value.
+    ///
+    /// It searches the tree once, and the key is only converted to an owned key when it's inserted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let text = "the quick brown fox jumps over the lazy dog the end";
+    ///
+    /// let mut histogram: RbTree<String, usize> = RbTree::new();
+    /// for word in text.split_whitespace() {
+    ///     histogram.increment(word, 1);
+    /// }
+    ///
+    /// assert_eq!(histogram["the"], 3);
+    /// assert_eq!(histogram["fox"], 1);
+    /// assert_eq!(histogram.len(), 9);
+    /// assert_eq!(histogram.iter().map(|(_, count)| count).sum::<usize>(), 11);
+    ///
+    /// assert_eq!(histogram.increment("the", 10), 13);
+    /// assert_eq!(histogram.increment("cat", 2), 2);
+    /// ```
+    pub fn increment<Q>(&mut self, key: &Q, by: V) -> V
+    where
+        K: Borrow<Q>,
+        Q: Ord + ToOwned<Owned = K> + ?Sized,
+        V: Add<Output = V> + Copy,
+    {
+        let cur = self.find_nearest_node(key);
 
-                target = right_min;
-            }
+        if cur.is_nil() {
+            self.insert_at(cur, key.to_owned(), by);
+
+            return by;
         }
 
-        target.key_value_moved = true;
+        let value = unsafe { cur.value_mut() };
+        *value = *value + by;
+        let total = *value;
+        self.augment_path(Some(cur));
 
-        let mut child = match target.left.unwrap().is_nil() {
-            true => target.right.unwrap(),
-            false => target.left.unwrap(),
+        total
+    }
+
+    /// Inserts all key-value pairs from `iter`, and returns how many existing keys were overwritten.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// tree.insert(1, "a");
+    ///
+    /// let overwritten = tree.insert_many([(1, "A"), (2, "b"), (3, "c"), (2, "B")]);
+    ///
+    /// assert_eq!(overwritten, 2);
+    /// assert!(tree.iter().eq([(&1, &"A"), (&2, &"B"), (&3, &"c")]));
+    /// ```
+    pub fn insert_many<I>(&mut self, iter: I) -> usize
+    where
+        K: Ord,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut overwritten = 0;
+
+        for (key, value) in iter {
+            if self.insert(key, value).is_some() {
+                overwritten += 1;
+            }
+        }
+
+        overwritten
+    }
+
+    /// Inserts key-value pairs from `sorted` in ascending key order, overwriting the values of existing keys.
+    ///
+    /// Each search starts from the node of the previous key instead of the root,
+    /// so merging a sorted stream of nearby keys is much faster than inserting one by one.
+    /// Keys not in ascending order are still inserted correctly, searching from the root.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut merged = RbTree::new();
+    /// let mut naive = RbTree::new();
+    /// for key in (0..100).step_by(3) {
+    ///     merged.insert(key, 0);
+    ///     naive.insert(key, 0);
+    /// }
+    ///
+    /// let log: Vec<_> = (50..150).step_by(2).map(|key| (key, 1)).collect();
+    ///
+    /// merged.merge_sorted(log.clone());
+    /// for (key, value) in log {
+    ///     naive.insert(key, value);
+    /// }
+    ///
+    /// assert!(merged.iter().eq(naive.iter()));
+    /// assert!(merged.is_correct_rb_tree());
+    /// ```
+    pub fn merge_sorted<I>(&mut self, sorted: I)
+    where
+        K: Ord,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut finger: Option<RbNode<K, V>> = None;
+
+        for (key, value) in sorted {
+            let mut cur = match finger {
+                Some(node) if key > *node.key() => self.find_nearest_node_after(node, &key),
+                _ => self.find_nearest_node(&key),
+            };
+
+            if cur.is_nil() {
+                self.insert_at(cur, key, value);
+            } else {
+                let old_value = unsafe { cur.value.assume_init_read() };
+                cur.value.write(value);
+                self.augment_path(Some(cur));
+
+                drop(old_value);
+            }
+
+            // rotations only relink nodes, so `cur` still holds `key`.
+            finger = Some(cur);
+        }
+    }
+
+    /// Moves all key-value pairs from `other` into `self`, leaving `other` empty.
+    ///
+    /// If a key from `other` is already in `self`, the value from `other` overwrites it.
+    ///
+    /// Entries are moved one by one in ascending key order. If comparing keys panics, the entry being moved
+    /// is dropped, and both trees stay valid, with the moved entries in `self` and the rest in `other`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::cell::Cell;
+    /// use std::cmp::Ordering;
+    /// use std::panic::{self, AssertUnwindSafe};
+    /// use std::rc::Rc;
+    /// use rbtree::RbTree;
+    ///
+    /// thread_local! {
+    ///     // comparisons left before `cmp` panics
+    ///     static BUDGET: Cell<usize> = Cell::new(usize::MAX);
+    /// }
+    ///
+    /// #[derive(PartialEq, Eq)]
+    /// struct Key(i32);
+    ///
+    /// impl PartialOrd for Key {
+    ///     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    ///         Some(self.cmp(other))
+    ///     }
+    /// }
+    ///
+    /// impl Ord for Key {
+    ///     fn cmp(&self, other: &Self) -> Ordering {
+    ///         BUDGET.with(|budget| {
+    ///             assert!(budget.get() > 0, "comparison budget exhausted");
+    ///             budget.set(budget.get() - 1);
+    ///         });
+    ///
+    ///         self.0.cmp(&other.0)
+    ///     }
+    /// }
+    ///
+    /// let counter = Rc::new(());
+    /// let mut tree = RbTree::new();
+    /// for key in 0..100 {
+    ///     tree.insert(Key(key * 2), Rc::clone(&counter));
+    /// }
+    ///
+    /// // insert: the search panics before the tree is modified.
+    /// BUDGET.with(|budget| budget.set(3));
+    /// let result = panic::catch_unwind(AssertUnwindSafe(|| tree.insert(Key(51), Rc::clone(&counter))));
+    /// assert!(result.is_err());
+    /// BUDGET.with(|budget| budget.set(usize::MAX));
+    ///
+    /// assert_eq!(tree.len(), 100);
+    /// assert!(tree.is_correct_rb_tree());
+    /// assert!(!tree.contains_key(&Key(51)));
+    ///
+    /// // append: panics partway, leaving the entries not moved yet in `other`.
+    /// let mut other = RbTree::new();
+    /// for key in 0..50 {
+    ///     other.insert(Key(key * 2 + 1), Rc::clone(&counter));
+    /// }
+    ///
+    /// BUDGET.with(|budget| budget.set(200));
+    /// let result = panic::catch_unwind(AssertUnwindSafe(|| tree.append(&mut other)));
+    /// assert!(result.is_err());
+    /// BUDGET.with(|budget| budget.set(usize::MAX));
+    ///
+    /// assert!(tree.is_correct_rb_tree());
+    /// assert!(other.is_correct_rb_tree());
+    /// assert!(tree.len() > 100 && !other.is_empty());
+    /// assert_eq!(tree.len() + other.len(), 149);
+    /// assert!(tree.pairs().all(|((a, _), (b, _))| a.0 < b.0));
+    ///
+    /// // nothing is leaked or dropped twice.
+    /// assert_eq!(Rc::strong_count(&counter), 150);
+    /// drop((tree, other));
+    /// assert_eq!(Rc::strong_count(&counter), 1);
+    /// ```
+    pub fn append(&mut self, other: &mut RbTree<K, V, A>)
+    where
+        K: Ord,
+    {
+        while let Some(first) = other.first_node() {
+            let ((key, value), _) = other.remove_node(first);
+            self.insert(key, value);
+        }
+    }
+
+    /// Moves all key-value pairs from `other` into `self`, leaving `other` empty.
+    ///
+    /// If a key is in both trees, `resolve` is called with the key, the value from `self`
+    /// and the value from `other`, and its result is stored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut a = RbTree::new();
+    /// a.insert(1, 10);
+    /// a.insert(2, 20);
+    ///
+    /// let mut b = RbTree::new();
+    /// b.insert(2, 5);
+    /// b.insert(3, 30);
+    ///
+    /// a.append_with(&mut b, |_, left, right| left.max(right));
+    ///
+    /// assert!(b.is_empty());
+    /// assert!(a.iter().eq([(&1, &10), (&2, &20), (&3, &30)]));
+    /// ```
+    ///
+    /// If `resolve` panics, the entry of its key is removed from `self`, since both values were moved into it.
+    /// The rest of the entries stay in either tree.
+    ///
+    /// ```
+    /// use std::panic::{self, AssertUnwindSafe};
+    /// use rbtree::RbTree;
+    ///
+    /// let mut a = RbTree::new();
+    /// let mut b = RbTree::new();
+    /// for key in 0..10 {
+    ///     a.insert(key, key);
+    ///     b.insert(key + 5, key + 5);
+    /// }
+    ///
+    /// let result = panic::catch_unwind(AssertUnwindSafe(|| {
+    ///     a.append_with(&mut b, |key, left, right| {
+    ///         assert!(*key < 7);
+    ///         left + right
+    ///     });
+    /// }));
+    ///
+    /// assert!(result.is_err());
+    /// assert!(a.iter().map(|(key, _)| *key).eq((0..7).chain(8..10)));
+    /// assert!(b.iter().map(|(key, _)| *key).eq(8..15));
+    /// assert!(a.is_correct_rb_tree() && b.is_correct_rb_tree());
+    /// ```
+    pub fn append_with<F>(&mut self, other: &mut RbTree<K, V, A>, mut resolve: F)
+    where
+        K: Ord,
+        F: FnMut(&K, V, V) -> V,
+    {
+        while let Some(first) = other.first_node() {
+            let ((key, value), _) = other.remove_node(first);
+            let mut cur = self.find_nearest_node(&key);
+
+            if cur.is_nil() {
+                self.insert_at(cur, key, value);
+                continue;
+            }
+
+            // the stored value is moved into `resolve`, so the guard removes its node if `resolve` panics.
+            let guard = RemoveOnUnwind {
+                tree: self,
+                node: cur,
+            };
+            let old_value = unsafe { cur.value.assume_init_read() };
+            let value = resolve(cur.key(), old_value, value);
+            std::mem::forget(guard);
+
+            cur.value.write(value);
+            self.augment_path(Some(cur));
+        }
+    }
+
+    /// Removes a node by key and returns its value.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, value)| value)
+    }
+
+    /// Removes a node by key and returns its key-value pair.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let target = self.find_nearest_node(key);
+
+        if target.is_nil() {
+            return None;
+        }
+
+        let (removed_key_value, _) = self.remove_node(target);
+
+        Some(removed_key_value)
+    }
+
+    /// Removes non-Nil node `target` from the tree and returns its key-value pair,
+    /// along with the node which holds the next entry in key order.
+    ///
+    /// If `target` has non-Nil right child, the next entry is moved into `target` and `target` is returned.
+    /// Otherwise `target` is released, and the next node is one of its ancestors.
+    #[allow(clippy::type_complexity)]
+    fn remove_node(&mut self, mut target: RbNode<K, V>) -> ((K, V), Option<RbNode<K, V>>) {
+        self.invalidate_lookup_cache();
+
+        let right_min = RbTree::min_node(target.right.unwrap());
+
+        let next = match right_min.is_nil() {
+            true => RbTree::next_node(target),
+            false => Some(target),
+        };
+
+        let removed_key_value = unsafe {
+            (
+                target.key.assume_init_read(),
+                target.value.assume_init_read(),
+            )
+        };
+
+        if !right_min.is_nil() {
+            unsafe {
+                target.key.write(right_min.key.assume_init_read());
+                target.value.write(right_min.value.assume_init_read());
+
+                target = right_min;
+            }
+        }
+
+        target.key_value_moved = true;
+
+        let mut child = match target.left.unwrap().is_nil() {
+            true => target.right.unwrap(),
+            false => target.left.unwrap(),
         };
 
         // replace target to child
@@ -224,20 +1185,29 @@ impl<K, V> RbTree<K, V> {
             target.right = None;
         }
 
+        let mut ancestor = child.parent;
+        while let Some(mut node) = ancestor {
+            node.size -= 1;
+            ancestor = node.parent;
+        }
+
+        // `target` had the successor moved in, and is on this path if it's kept.
+        self.augment_path(child.parent);
+
         let target_rb_node_type = target.rb_node_type;
 
         // release target
-        target.uninit();
-        let _ = unsafe { Box::from_raw(target.as_ptr()) };
+        target.uninit(&mut self.pool);
+        self.pool.free(target);
 
         self.len -= 1;
 
         match target_rb_node_type {
-            RbNodeType::Red => return Some(removed_key_value),
+            RbNodeType::Red => return (removed_key_value, next),
             RbNodeType::Black => {
                 if child.is_red() {
                     child.set_black();
-                    return Some(removed_key_value);
+                    return (removed_key_value, next);
                 }
             }
             _ => unreachable!(),
@@ -251,7 +1221,7 @@ impl<K, V> RbTree<K, V> {
             parent = match node.parent {
                 Some(parent) => parent,
                 // case 1: node is root
-                None => return Some(removed_key_value),
+                None => return (removed_key_value, next),
             };
 
             sibling = if parent.left == Some(node) {
@@ -301,7 +1271,7 @@ impl<K, V> RbTree<K, V> {
             sibling.set_red();
             parent.set_black();
 
-            return Some(removed_key_value);
+            return (removed_key_value, next);
         }
 
         // case 5
@@ -341,7 +1311,51 @@ impl<K, V> RbTree<K, V> {
             self.rotate_right(parent);
         }
 
-        Some(removed_key_value)
+        (removed_key_value, next)
+    }
+
+    /// find the node with key, or `None` if the key is not in the tree.
+    ///
+    /// All key accessors like `get` and `contains_key` are built on it.
+    fn lookup<Q>(&self, key: &Q) -> Option<RbNode<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let node = self.find_nearest_node_cached(key);
+
+        (!node.is_nil()).then_some(node)
+    }
+
+    /// find the node with key like `find_nearest_node`, checking the lookup cache first if it's enabled.
+    fn find_nearest_node_cached<Q>(&self, key: &Q) -> RbNode<K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let Some(cache) = &self.lookup_cache else {
+            return self.find_nearest_node(key);
+        };
+
+        if let Some(node) = cache.get() {
+            if key.cmp(node.key().borrow()) == Ordering::Equal {
+                #[cfg(feature = "metrics")]
+                {
+                    self.lookup_comparisons.set(1);
+                    self.lookup_count.set(self.lookup_count.get() + 1);
+                }
+
+                return node;
+            }
+        }
+
+        let node = self.find_nearest_node(key);
+
+        if !node.is_nil() {
+            cache.set(Some(node));
+        }
+
+        node
     }
 
     /// find the node with key or Nil node with proper place to insert.
@@ -350,69 +1364,126 @@ impl<K, V> RbTree<K, V> {
         K: Borrow<Q>,
         Q: Ord + ?Sized,
     {
-        let mut cur = self.root;
+        self.find_nearest_node_from(self.root, key)
+    }
+
+    /// find the node with key or Nil node with proper place to insert, descending from `start`.
+    ///
+    /// `key` must belong to the subtree of `start`.
+    fn find_nearest_node_from<Q>(&self, start: RbNode<K, V>, key: &Q) -> RbNode<K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.find_nearest_node_by(start, |other| key.cmp(other.borrow()))
+    }
+
+    /// find the node for which `cmp` returns `Equal`, or Nil node with proper place to insert, descending from `start`.
+    ///
+    /// `cmp` returns the ordering of the searched key relative to the given key.
+    fn find_nearest_node_by<F>(&self, start: RbNode<K, V>, mut cmp: F) -> RbNode<K, V>
+    where
+        F: FnMut(&K) -> Ordering,
+    {
+        let mut cur = start;
+
+        #[cfg(feature = "metrics")]
+        let mut comparisons = 0;
 
         loop {
             if cur.is_nil() {
                 break;
             }
 
-            match cur.key() {
-                x if key < x.borrow() => {
+            #[cfg(feature = "metrics")]
+            {
+                comparisons += 1;
+            }
+
+            match cmp(cur.key()) {
+                Ordering::Less => {
                     cur = cur.left.unwrap();
                 }
-                x if key > x.borrow() => {
+                Ordering::Greater => {
                     cur = cur.right.unwrap();
                 }
-                _ => break,
+                Ordering::Equal => break,
             }
         }
 
+        #[cfg(feature = "metrics")]
+        {
+            self.lookup_comparisons.set(comparisons);
+            self.lookup_count.set(self.lookup_count.get() + 1);
+        }
+
         cur
     }
 
-    /// find left-most non-Nil node starting from input node.
+    /// find the node with key or Nil node with proper place to insert, climbing up from `finger` first.
     ///
-    /// It returns Nil only if input node is Nil.
-    fn min_node(node: RbNode<K, V>) -> RbNode<K, V> {
-        if node.is_nil() {
-            return node;
-        }
+    /// `key` must be greater than the key of `finger`.
+    /// It's faster than searching from the root when `key` is close to the key of `finger`.
+    fn find_nearest_node_after(&self, finger: RbNode<K, V>, key: &K) -> RbNode<K, V>
+    where
+        K: Ord,
+    {
+        let mut cur = finger;
+
+        while let Some(parent) = cur.parent {
+            if parent.left == Some(cur) {
+                match key.cmp(parent.key()) {
+                    Ordering::Less => break,
+                    Ordering::Equal => return parent,
+                    Ordering::Greater => {}
+                }
+            }
 
-        let mut cur = node;
+            cur = parent;
+        }
 
-        loop {
-            let left = cur.left.unwrap();
-            if left.is_nil() {
-                break;
-            }
+        self.find_nearest_node_from(cur, key)
+    }
 
-            cur = left;
+    /// find the left-most non-Nil node of the tree, or `None` if the tree is empty.
+    fn first_node(&self) -> Option<RbNode<K, V>> {
+        match self.is_empty() {
+            true => None,
+            false => Some(RbTree::min_node(self.root)),
         }
+    }
 
-        cur
+    /// find the right-most non-Nil node of the tree, or `None` if the tree is empty.
+    fn last_node(&self) -> Option<RbNode<K, V>> {
+        match self.is_empty() {
+            true => None,
+            false => Some(RbTree::max_node(self.root)),
+        }
     }
 
-    /// find right-most non-Nil node starting from input node.
+    /// find the node at `index` in key order using subtree sizes.
     ///
-    /// It returns Nil only if input node is Nil.
-    fn max_node(node: RbNode<K, V>) -> RbNode<K, V> {
-        if node.is_nil() {
-            return node;
+    /// It returns `None` if `index` is out of range.
+    fn select_node(&self, index: usize) -> Option<RbNode<K, V>> {
+        if index >= self.len {
+            return None;
         }
 
-        let mut cur = node;
+        let mut index = index;
+        let mut cur = self.root;
 
         loop {
-            let right = cur.right.unwrap();
-            if right.is_nil() {
-                break;
-            }
+            let left = cur.left.unwrap();
 
-            cur = right;
+            match index {
+                x if x < left.size => cur = left,
+                x if x == left.size => return Some(cur),
+                _ => {
+                    index -= left.size + 1;
+                    cur = cur.right.unwrap();
+                }
+            }
         }
-
-        cur
     }
 
     /// Rotate tree to left from input node.
@@ -421,6 +1492,11 @@ impl<K, V> RbTree<K, V> {
     ///
     /// Panics if `node.right` is `None`
     fn rotate_left(&mut self, mut node: RbNode<K, V>) {
+        #[cfg(feature = "metrics")]
+        {
+            self.rotation_count += 1;
+        }
+
         let mut right = node.right.expect("Right Child should not be None");
 
         let parent = node.parent;
@@ -434,6 +1510,11 @@ impl<K, V> RbTree<K, V> {
         right.left = Some(node);
         right.parent = parent;
 
+        right.size = node.size;
+        node.update_size();
+        Self::augment_node_with(node, A::on_rotate);
+        Self::augment_node_with(right, A::on_rotate);
+
         if let Some(mut parent) = parent {
             if parent.left == Some(node) {
                 parent.left = Some(right);
@@ -451,6 +1532,11 @@ impl<K, V> RbTree<K, V> {
     ///
     /// Panics if `node.left` is `None` that is node is Nil
     fn rotate_right(&mut self, mut node: RbNode<K, V>) {
+        #[cfg(feature = "metrics")]
+        {
+            self.rotation_count += 1;
+        }
+
         let mut left = node.left.expect("Left Child should not be None");
 
         let parent = node.parent;
@@ -464,6 +1550,11 @@ impl<K, V> RbTree<K, V> {
         left.right = Some(node);
         left.parent = parent;
 
+        left.size = node.size;
+        node.update_size();
+        Self::augment_node_with(node, A::on_rotate);
+        Self::augment_node_with(left, A::on_rotate);
+
         if let Some(mut parent) = parent {
             if parent.right == Some(node) {
                 parent.right = Some(left);
@@ -475,163 +1566,3723 @@ impl<K, V> RbTree<K, V> {
         }
     }
 
-    /// Check if the tree complies RB-Tree attributes.
-    pub fn is_correct_rb_tree(&self) -> bool {
-        match RbTree::check_rb_tree_attribute(self.root) {
-            Ok(_) => true,
-            Err(_) => false,
+    /// Returns the number of nodes on the longest path from the root to a leaf.
+    ///
+    /// It's 0 for the empty tree.
+    pub fn height(&self) -> usize {
+        let mut height = 0;
+        let mut stack = vec![(self.root, 0)];
+
+        while let Some((node, depth)) = stack.pop() {
+            if node.is_nil() {
+                height = height.max(depth);
+                continue;
+            }
+
+            stack.push((node.left.unwrap(), depth + 1));
+            stack.push((node.right.unwrap(), depth + 1));
         }
+
+        height
     }
 
-    fn check_rb_tree_attribute(node: RbNode<K, V>) -> Result<u64, ()> {
-        let left_black_count = match node.left {
-            Some(left) => RbTree::check_rb_tree_attribute(left)?,
-            None => 0,
-        };
+    /// Returns the number of black nodes on the path from the root to the left-most leaf, excluding Nil nodes.
+    ///
+    /// Every path from the root to a leaf has the same number of black nodes in a correct RB-Tree.
+    pub fn black_height(&self) -> usize {
+        let mut black_height = 0;
+        let mut cur = self.root;
 
-        let right_black_count = match node.right {
-            Some(right) => RbTree::check_rb_tree_attribute(right)?,
-            None => 0,
-        };
+        while !cur.is_nil() {
+            if cur.is_black() {
+                black_height += 1;
+            }
 
-        if left_black_count != right_black_count {
-            return Err(());
+            cur = cur.left.unwrap();
         }
 
-        let self_black_count = match node.is_black() {
-            true => 1,
-            false => 0,
-        };
-
-        Ok(left_black_count + self_black_count)
-    }
-
-    pub fn len(&self) -> usize {
-        self.len
+        black_height
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.len == 0
+    /// Returns the number of edges from the root to the node of the key, or `None` if the key is not in the tree.
+    ///
+    /// The root is at depth 0, and every key is at a depth less than `height`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// //       4
+    /// //    2     6
+    /// //   1 3   5 7
+    /// let tree = RbTree::from_sorted_iter((1..=7).map(|key| (key, ())));
+    ///
+    /// assert_eq!(tree.depth_of(&4), Some(0));
+    /// assert_eq!(tree.depth_of(&6), Some(1));
+    /// assert_eq!(tree.depth_of(&1), Some(2));
+    /// assert_eq!(tree.depth_of(&8), None);
+    /// assert!((1..=7).all(|key| tree.depth_of(&key).unwrap() < tree.height()));
+    /// ```
+    pub fn depth_of<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut cur = self.lookup(key)?;
+        let mut depth = 0;
+
+        while let Some(parent) = cur.parent {
+            depth += 1;
+            cur = parent;
+        }
+
+        Some(depth)
+    }
+
+    /// Panics if the tree is taller than twice its black height, which the RB-Tree attributes guarantee.
+    ///
+    /// It's compiled only with `debug_assertions` or the `testing` feature, for property tests.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 0..10000 {
+    ///     tree.insert(key, ());
+    /// }
+    /// for key in (0..10000).step_by(3) {
+    ///     tree.remove(&key);
+    /// }
+    ///
+    /// tree.assert_balanced();
+    /// ```
+    #[cfg(any(debug_assertions, feature = "testing"))]
+    #[track_caller]
+    pub fn assert_balanced(&self) {
+        let height = self.height();
+        let black_height = self.black_height();
+
+        assert!(
+            height <= 2 * black_height,
+            "tree is unbalanced: height {height} > 2 * black height {black_height}"
+        );
+    }
+
+    /// Returns the number of key comparisons performed by the last lookup.
+    ///
+    /// Every method searching a key by a single descent from the root, like `get` or `insert`, is a lookup.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 0..1000 {
+    ///     tree.insert(key, ());
+    /// }
+    ///
+    /// for key in [0, 500, 999, 1000] {
+    ///     tree.contains_key(&key);
+    ///     assert!(tree.last_lookup_comparisons() <= tree.height() + 1);
+    /// }
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn last_lookup_comparisons(&self) -> usize {
+        self.lookup_comparisons.get()
+    }
+
+    /// Returns the number of lookups performed since the tree was created.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut counts = RbTree::new();
+    /// for key in 0..100 {
+    ///     counts.insert(key, 0);
+    /// }
+    ///
+    /// // `entry` keeps the found node, so `or_insert` doesn't search again.
+    /// for key in [5, 50, 500] {
+    ///     let before = counts.lookup_count();
+    ///     *counts.entry(key).or_insert(0) += 1;
+    ///     assert_eq!(counts.lookup_count() - before, 1);
+    /// }
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn lookup_count(&self) -> usize {
+        self.lookup_count.get()
+    }
+
+    /// Returns the number of rotations performed since the tree was created or `reset_rotation_count` was called.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 0..1000 {
+    ///     tree.insert(key, ());
+    /// }
+    ///
+    /// // sorted insertion rotates often, but at most twice per insertion.
+    /// assert!(tree.rotation_count() > 0);
+    /// assert!(tree.rotation_count() <= 2 * 1000);
+    ///
+    /// tree.reset_rotation_count();
+    /// assert_eq!(tree.rotation_count(), 0);
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn rotation_count(&self) -> u64 {
+        self.rotation_count
+    }
+
+    /// Resets the number of rotations returned by `rotation_count` to 0.
+    #[cfg(feature = "metrics")]
+    pub fn reset_rotation_count(&mut self) {
+        self.rotation_count = 0;
+    }
+
+    /// Check if the tree complies RB-Tree attributes.
+    ///
+    /// It doesn't recurse, so its stack usage doesn't depend on the height of the tree,
+    /// even when a broken tree is much higher than a correct one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::thread;
+    /// use rbtree::RbTree;
+    ///
+    /// // a correct tree is at most about 2 log n high, so this is only a smoke test.
+    /// // the unit tests check a broken tree which is as high as it's long.
+    /// let is_correct = thread::Builder::new()
+    ///     .stack_size(64 * 1024)
+    ///     .spawn(|| {
+    ///         let mut tree = RbTree::new();
+    ///         for key in 0..200_000 {
+    ///             tree.insert(key, ());
+    ///         }
+    ///
+    ///         tree.is_correct_rb_tree()
+    ///     })
+    ///     .unwrap()
+    ///     .join()
+    ///     .unwrap();
+    ///
+    /// assert!(is_correct);
+    /// ```
+    pub fn is_correct_rb_tree(&self) -> bool {
+        RbTree::check_rb_tree_attribute(self.root).is_ok()
+    }
+
+    /// Returns a Graphviz DOT representation of the tree, with colored nodes and labeled left/right edges.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 1..=4 {
+    ///     tree.insert(key, ());
+    /// }
+    ///
+    /// let dot = tree.to_dot();
+    ///
+    /// assert!(dot.starts_with("digraph RbTree {\n"));
+    /// assert!(dot.ends_with("}\n"));
+    /// assert_eq!(dot.matches("fillcolor=").count(), 4);
+    /// assert_eq!(dot.matches(" -> ").count(), 3);
+    /// assert!(dot.contains("n0 [label=\"2\", fillcolor=black];"));
+    /// assert!(dot.contains("fillcolor=red"));
+    /// ```
+    pub fn to_dot(&self) -> String
+    where
+        K: Debug,
+    {
+        let mut dot = String::from("digraph RbTree {\n    node [style=filled, fontcolor=white];\n");
+
+        let mut next_id = 0;
+        let mut stack = Vec::new();
+
+        if !self.root.is_nil() {
+            stack.push((self.root, next_id));
+            next_id += 1;
+        }
+
+        while let Some((node, id)) = stack.pop() {
+            let color = match node.is_red() {
+                true => "red",
+                false => "black",
+            };
+            let label = format!("{:?}", node.key());
+
+            dot.push_str(&format!(
+                "    n{id} [label={label:?}, fillcolor={color}];\n"
+            ));
+
+            for (child, side) in [(node.left, "L"), (node.right, "R")] {
+                let child = child.unwrap();
+
+                if !child.is_nil() {
+                    dot.push_str(&format!("    n{id} -> n{next_id} [label={side}];\n"));
+                    stack.push((child, next_id));
+                    next_id += 1;
+                }
+            }
+        }
+
+        dot.push_str("}\n");
+
+        dot
+    }
+
+    /// Checks all RB-Tree invariants and the key order, and rebuilds the tree from its entries if any is broken.
+    ///
+    /// The entries are sorted by key before rebuilding, and only the first of duplicated keys is kept.
+    /// It's a last-resort tool for debugging trees corrupted by unsafe code or by keys whose order changed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::cell::Cell;
+    /// use rbtree::RbTree;
+    ///
+    /// #[derive(PartialEq, Eq, PartialOrd, Ord)]
+    /// struct Key(Cell<i32>);
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 0..10 {
+    ///     tree.insert(Key(Cell::new(key)), key);
+    /// }
+    ///
+    /// assert!(tree.validate_and_repair().is_clean());
+    ///
+    /// // break the key order through interior mutability.
+    /// tree.iter().next().unwrap().0 .0.set(100);
+    ///
+    /// let report = tree.validate_and_repair();
+    /// assert!(!report.is_clean());
+    /// assert_eq!(report.misordered_pairs, 1);
+    /// assert!(report.rebuilt);
+    ///
+    /// assert!(tree.validate_and_repair().is_clean());
+    /// assert!(tree.iter().map(|(_, value)| *value).eq([1, 2, 3, 4, 5, 6, 7, 8, 9, 0]));
+    /// assert!(tree.is_correct_rb_tree());
+    /// ```
+    pub fn validate_and_repair(&mut self) -> RepairReport
+    where
+        K: Ord,
+    {
+        let (mut report, count) = self.check_invariants();
+
+        if report.is_clean() {
+            return report;
+        }
+
+        let mut entries = self.take_entries();
+        entries.len = count;
+
+        let mut entries: Vec<_> = entries.into_iter().collect();
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let before = entries.len();
+        entries.dedup_by(|(a, _), (b, _)| a == b);
+        report.removed_duplicates = before - entries.len();
+
+        self.fill_sorted(entries);
+        report.rebuilt = true;
+
+        report
+    }
+
+    /// Checks all RB-Tree invariants and the key order without repairing,
+    /// and returns the report with the number of nodes actually reachable from the root.
+    fn check_invariants(&self) -> (RepairReport, usize)
+    where
+        K: Ord,
+    {
+        let mut report = RepairReport {
+            black_height_mismatch: !self.is_correct_rb_tree(),
+            red_violations: 0,
+            size_mismatches: 0,
+            len_mismatch: false,
+            misordered_pairs: 0,
+            removed_duplicates: 0,
+            rebuilt: false,
+        };
+
+        if self.root.is_red() {
+            report.red_violations += 1;
+        }
+
+        let mut count = 0;
+        let mut cur = self.root;
+        let mut stack = self.new_iter_stack();
+
+        while let Some((next, node)) = iter_next(cur, &mut stack) {
+            cur = next;
+            count += 1;
+
+            if node.is_red() && node.parent.is_some_and(|parent| parent.is_red()) {
+                report.red_violations += 1;
+            }
+
+            if node.size != node.left.unwrap().size + node.right.unwrap().size + 1 {
+                report.size_mismatches += 1;
+            }
+        }
+
+        report.len_mismatch = count != self.len;
+        report.misordered_pairs = self.pairs().filter(|((a, _), (b, _))| a >= b).count();
+
+        (report, count)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::IndexMut;
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for name in ["alice", "bob", "carol"] {
+    ///     tree.insert(name.to_string(), name.len());
+    /// }
+    ///
+    /// // all key accessors agree, with `&str` and `&String` keys alike.
+    /// for name in ["alice", "bob", "carol", "dave"] {
+    ///     let owned = name.to_string();
+    ///     let expected = tree.get_key_value(name).map(|(_, value)| *value);
+    ///
+    ///     assert_eq!(tree.get(name).copied(), expected);
+    ///     assert_eq!(tree.get(&owned).copied(), expected);
+    ///     assert_eq!(tree.get_mut(name).map(|value| *value), expected);
+    ///     assert_eq!(tree.contains_key(name), expected.is_some());
+    ///     assert_eq!(tree.contains_key(&owned), expected.is_some());
+    ///
+    ///     if let Some(value) = expected {
+    ///         assert_eq!(tree.get_key_value(&owned), Some((&owned, &value)));
+    ///         assert_eq!(tree[name], value);
+    ///         assert_eq!(tree[&owned], value);
+    ///         assert_eq!(*tree.index_mut(name), value);
+    ///     }
+    /// }
+    /// ```
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get_key_value(key).map(|(_, value)| value)
+    }
+
+    /// Returns a reference to the key-value pair corresponding to the key.
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let target = self.lookup(key)?;
+
+        unsafe { Some(target.key_value_ref()) }
+    }
+
+    /// Applies `f` to the value corresponding to the key, and returns whether the key is in the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut scores = RbTree::new();
+    /// scores.insert("alice", 10);
+    ///
+    /// assert!(scores.update("alice", |score| *score += 5));
+    /// assert_eq!(scores["alice"], 15);
+    ///
+    /// assert!(!scores.update("bob", |score| *score += 5));
+    /// assert!(!scores.contains_key("bob"));
+    /// ```
+    pub fn update<Q, F>(&mut self, key: &Q, f: F) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        F: FnOnce(&mut V),
+    {
+        match self.lookup(key) {
+            Some(node) => {
+                let _augment = AugmentOnDrop {
+                    tree: self,
+                    node: Some(node),
+                };
+                f(unsafe { node.value_mut() });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Swaps the values of two keys in place, without restructuring the tree.
+    ///
+    /// Returns `false` if either key is not in the tree. Swapping a key with itself does nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// tree.insert(1, "a");
+    /// tree.insert(2, "b");
+    ///
+    /// assert!(tree.swap_values(&1, &2));
+    /// assert_eq!(tree[&1], "b");
+    /// assert_eq!(tree[&2], "a");
+    ///
+    /// assert!(!tree.swap_values(&1, &3));
+    /// assert_eq!(tree[&1], "b");
+    /// ```
+    pub fn swap_values<Q>(&mut self, a: &Q, b: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (Some(mut a), Some(mut b)) = (self.lookup(a), self.lookup(b)) else {
+            return false;
+        };
+
+        if a != b {
+            std::mem::swap(&mut a.value, &mut b.value);
+            self.augment_path(Some(a));
+            self.augment_path(Some(b));
+        }
+
+        true
+    }
+
+    /// Check if tree has a node with input key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.lookup(key).is_some()
+    }
+
+    /// Check if tree has nodes with all of input keys, stopping at the first missing key.
+    ///
+    /// It returns `true` for no keys.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in ["host", "port", "user"] {
+    ///     tree.insert(key.to_string(), ());
+    /// }
+    ///
+    /// assert!(tree.contains_all(["host", "port"]));
+    /// assert!(!tree.contains_all(["host", "password"]));
+    /// assert!(tree.contains_all(Vec::<&str>::new()));
+    ///
+    /// assert!(tree.contains_any(["password", "user"]));
+    /// assert!(!tree.contains_any(["password", "token"]));
+    /// assert!(!tree.contains_any(Vec::<&str>::new()));
+    /// ```
+    pub fn contains_all<'q, Q, I>(&self, keys: I) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized + 'q,
+        I: IntoIterator<Item = &'q Q>,
+    {
+        keys.into_iter().all(|key| self.contains_key(key))
+    }
+
+    /// Check if tree has a node with any of input keys, stopping at the first present key.
+    ///
+    /// It returns `false` for no keys.
+    pub fn contains_any<'q, Q, I>(&self, keys: I) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized + 'q,
+        I: IntoIterator<Item = &'q Q>,
+    {
+        keys.into_iter().any(|key| self.contains_key(key))
+    }
+
+    /// Check if tree has a node with input value.
+    ///
+    /// Values aren't ordered in the tree, so every value may be compared before the answer is known.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// tree.insert(1, "a");
+    /// tree.insert(2, "b");
+    ///
+    /// assert!(tree.contains_value(&"b"));
+    /// assert!(!tree.contains_value(&"c"));
+    /// ```
+    pub fn contains_value(&self, value: &V) -> bool
+    where
+        V: PartialEq,
+    {
+        self.iter().any(|(_, v)| v == value)
+    }
+
+    /// Returns the first key-value pair in key order whose value satisfies `pred`.
+    ///
+    /// Like `contains_value` with a predicate, but it also tells which key holds the matching value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut scores = RbTree::new();
+    /// scores.insert("carol", 90);
+    /// scores.insert("alice", 70);
+    /// scores.insert("bob", 85);
+    /// scores.insert("dave", 95);
+    ///
+    /// assert_eq!(scores.find_by_value(|score| *score > 80), Some((&"bob", &85)));
+    /// assert_eq!(scores.find_by_value(|score| *score > 100), None);
+    /// ```
+    pub fn find_by_value<P>(&self, mut pred: P) -> Option<(&K, &V)>
+    where
+        P: FnMut(&V) -> bool,
+    {
+        self.iter().find(|(_, value)| pred(value))
+    }
+
+    /// Returns the key-value pair with the smallest key which satisfies `pred`.
+    ///
+    /// `pred` is called from the smallest key up, and not after the first match.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// // tasks by deadline, with whether each is done.
+    /// let mut tasks = RbTree::new();
+    /// for (deadline, done) in [(1, true), (2, true), (3, false), (4, true), (5, false), (6, true)] {
+    ///     tasks.insert(deadline, done);
+    /// }
+    ///
+    /// assert_eq!(tasks.min_entry_where(|_, done| !*done), Some((&3, &false)));
+    /// assert_eq!(tasks.min_entry_where(|deadline, _| *deadline > 10), None);
+    /// ```
+    pub fn min_entry_where<F>(&self, pred: F) -> Option<(&K, &V)>
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        self.find_entry_from(self.first_node(), RbTree::next_node, pred)
+    }
+
+    /// Returns the key-value pair with the largest key which satisfies `pred`.
+    ///
+    /// Like `min_entry_where`, but searching from the largest key down.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tasks = RbTree::new();
+    /// for (deadline, done) in [(1, true), (2, false), (3, false), (4, true), (5, true), (6, true)] {
+    ///     tasks.insert(deadline, done);
+    /// }
+    ///
+    /// assert_eq!(tasks.max_entry_where(|_, done| !*done), Some((&3, &false)));
+    /// assert_eq!(tasks.max_entry_where(|deadline, _| *deadline < 1), None);
+    /// assert_eq!(RbTree::<i32, bool>::new().max_entry_where(|_, _| true), None);
+    /// ```
+    pub fn max_entry_where<F>(&self, pred: F) -> Option<(&K, &V)>
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        self.find_entry_from(self.last_node(), RbTree::prev_node, pred)
+    }
+
+    /// find the first entry satisfying `pred`, visiting nodes from `start` with `advance`.
+    #[allow(clippy::type_complexity)]
+    fn find_entry_from<F>(
+        &self,
+        start: Option<RbNode<K, V>>,
+        advance: fn(RbNode<K, V>) -> Option<RbNode<K, V>>,
+        mut pred: F,
+    ) -> Option<(&K, &V)>
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        let mut cur = start;
+
+        while let Some(node) = cur {
+            let (key, value) = unsafe { node.key_value_ref() };
+
+            if pred(key, value) {
+                return Some((key, value));
+            }
+
+            cur = advance(node);
+        }
+
+        None
+    }
+
+    /// Returns the index of the first key for which `pred` returns `false`.
+    ///
+    /// `pred` must return `true` for a prefix of the keys in order and `false` for the rest,
+    /// like `slice::partition_point`. It takes O(log n) using subtree sizes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in (0..60).step_by(3) {
+    ///     tree.insert(key, ());
+    /// }
+    ///
+    /// // thresholds from a seeded LCG over -10..70, below the smallest and above the largest key.
+    /// let mut seed: u32 = 12345;
+    /// for _ in 0..200 {
+    ///     seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+    ///     let threshold = (seed >> 16) as i32 % 80 - 10;
+    ///
+    ///     let expected = tree.iter().take_while(|(key, _)| **key < threshold).count();
+    ///     assert_eq!(tree.partition_point(|key| *key < threshold), expected);
+    /// }
+    /// ```
+    pub fn partition_point<P>(&self, mut pred: P) -> usize
+    where
+        P: FnMut(&K) -> bool,
+    {
+        let mut index = 0;
+        let mut cur = self.root;
+
+        while !cur.is_nil() {
+            if pred(cur.key()) {
+                index += cur.left.unwrap().size + 1;
+                cur = cur.right.unwrap();
+            } else {
+                cur = cur.left.unwrap();
+            }
+        }
+
+        index
+    }
+
+    /// Returns the index of the key in key order, or `None` if the key is not in the tree.
+    ///
+    /// It takes O(log n) using subtree sizes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in (0..100).rev().step_by(7) {
+    ///     tree.insert(key, ());
+    /// }
+    ///
+    /// for (index, (key, _)) in tree.iter().enumerate() {
+    ///     assert_eq!(tree.position_of(key), Some(index));
+    /// }
+    ///
+    /// assert_eq!(tree.position_of(&2), None);
+    /// ```
+    pub fn position_of<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.lookup(key).map(RbTree::node_index)
+    }
+
+    /// Returns the index and the key-value pair of the first entry with key not less than `key`,
+    /// or `None` if there's no such entry.
+    ///
+    /// It takes a single descent using subtree sizes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in [10, 20, 30] {
+    ///     tree.insert(key, key / 10);
+    /// }
+    ///
+    /// assert_eq!(tree.get_at_or_after(&0), Some((0, &10, &1)));
+    /// assert_eq!(tree.get_at_or_after(&20), Some((1, &20, &2)));
+    /// assert_eq!(tree.get_at_or_after(&21), Some((2, &30, &3)));
+    /// assert_eq!(tree.get_at_or_after(&31), None);
+    /// ```
+    pub fn get_at_or_after<Q>(&self, key: &Q) -> Option<(usize, &K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut found = None;
+        let mut index = 0;
+        let mut cur = self.root;
+
+        while !cur.is_nil() {
+            let left = cur.left.unwrap();
+
+            if cur.key().borrow() >= key {
+                found = Some((index + left.size, cur));
+                cur = left;
+            } else {
+                index += left.size + 1;
+                cur = cur.right.unwrap();
+            }
+        }
+
+        let (index, node) = found?;
+
+        let (key, value) = unsafe { node.key_value_ref() };
+
+        Some((index, key, value))
+    }
+
+    /// Returns a cursor pointing at the entry at `index` in key order, or `None` if `index` is out of range.
+    ///
+    /// It takes O(log n) using subtree sizes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 0..100 {
+    ///     tree.insert(key * 10, ());
+    /// }
+    ///
+    /// for index in [0, 1, 42, 99] {
+    ///     let cursor = tree.cursor_at_index(index).unwrap();
+    ///     assert_eq!(cursor.key(), Some(&(index * 10)));
+    /// }
+    ///
+    /// let mut cursor = tree.cursor_at_index(50).unwrap();
+    /// cursor.move_next();
+    /// assert_eq!(cursor.key(), Some(&510));
+    /// cursor.move_prev();
+    /// cursor.move_prev();
+    /// assert_eq!(cursor.key(), Some(&490));
+    ///
+    /// assert!(tree.cursor_at_index(100).is_none());
+    /// ```
+    pub fn cursor_at_index(&self, index: usize) -> Option<Cursor<'_, K, V, A>> {
+        let node = self.select_node(index)?;
+
+        Some(Cursor {
+            tree: self,
+            current: Some(node),
+        })
+    }
+
+    /// Create an empty stack for in-order traversal, with capacity for the path from the root to the deepest leaf.
+    ///
+    /// RB-Tree with n nodes is at most 2 * log2(n + 1) high, so the stack never reallocates.
+    fn new_iter_stack<T>(&self) -> Vec<T> {
+        // the bit length of n is log2(n + 1) rounded up.
+        let log2_len = (usize::BITS - self.len.leading_zeros()) as usize;
+
+        Vec::with_capacity(2 * log2_len)
+    }
+
+    /// Returns an iterator over key-value pairs in ascending key order.
+    ///
+    /// The order depends only on the keys, so trees with the same entries always iterate identically,
+    /// regardless of insertion order or the shape of the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let ascending: Vec<i32> = (0..100).collect();
+    /// let descending: Vec<i32> = (0..100).rev().collect();
+    /// let interleaved: Vec<i32> = (0..50).flat_map(|key| [key, 99 - key]).collect();
+    /// let scattered: Vec<i32> = (0..100).map(|key| key * 37 % 100).collect();
+    ///
+    /// let orders = [ascending, descending, interleaved, scattered];
+    /// let expected: Vec<_> = (0..100).map(|key| (key, key * 2)).collect();
+    ///
+    /// for order in orders {
+    ///     let mut tree = RbTree::new();
+    ///     for key in order {
+    ///         tree.insert(key, key * 2);
+    ///     }
+    ///
+    ///     let entries: Vec<_> = tree.iter().map(|(key, value)| (*key, *value)).collect();
+    ///     assert_eq!(entries, expected);
+    /// }
+    /// ```
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            cur: self.root,
+            stack: self.new_iter_stack(),
+            len: self.len,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns an iterator over copies of key-value pairs in ascending key order, without consuming the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// tree.insert(2, 'b');
+    /// tree.insert(1, 'a');
+    ///
+    /// let copied: Vec<_> = tree.iter_copied().collect();
+    ///
+    /// assert_eq!(copied, [(1, 'a'), (2, 'b')]);
+    /// assert_eq!(tree.len(), 2);
+    /// ```
+    pub fn iter_copied(&self) -> impl Iterator<Item = (K, V)> + '_
+    where
+        K: Copy,
+        V: Copy,
+    {
+        self.iter().map(|(key, value)| (*key, *value))
+    }
+
+    /// Returns an iterator over key-value pairs with their depths from the root, in ascending key order.
+    ///
+    /// The root has depth 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 1..=3 {
+    ///     tree.insert(key, ());
+    /// }
+    ///
+    /// // 2 is the root, and 1, 3 are its children.
+    /// let depths: Vec<_> = tree.iter_with_depth().map(|(key, _, depth)| (*key, depth)).collect();
+    /// assert_eq!(depths, [(1, 1), (2, 0), (3, 1)]);
+    ///
+    /// for key in 4..100 {
+    ///     tree.insert(key, ());
+    /// }
+    ///
+    /// let max_depth = tree.iter_with_depth().map(|(_, _, depth)| depth).max().unwrap();
+    /// assert_eq!(max_depth + 1, tree.height());
+    /// ```
+    pub fn iter_with_depth(&self) -> impl Iterator<Item = (&K, &V, usize)> {
+        let mut cur = (self.root, 0);
+        let mut stack: Vec<(RbNode<K, V>, usize)> = self.new_iter_stack();
+
+        std::iter::from_fn(move || {
+            while !cur.0.is_nil() {
+                stack.push(cur);
+                cur = (cur.0.left.unwrap(), cur.1 + 1);
+            }
+
+            let (node, depth) = stack.pop()?;
+            cur = (node.right.unwrap(), depth + 1);
+
+            let (key, value) = unsafe { node.key_value_ref() };
+
+            Some((key, value, depth))
+        })
+    }
+
+    /// Returns an iterator over batches of at most `size` entries in ascending key order.
+    ///
+    /// Every batch has `size` entries except the last one, which may be smaller.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 0..10 {
+    ///     tree.insert(key, key * 10);
+    /// }
+    ///
+    /// let sizes: Vec<_> = tree.chunks(4).map(|chunk| chunk.len()).collect();
+    /// assert_eq!(sizes, [4, 4, 2]);
+    ///
+    /// assert!(tree.chunks(3).flatten().eq(tree.iter()));
+    /// assert_eq!(RbTree::<i32, i32>::new().chunks(3).count(), 0);
+    /// ```
+    #[track_caller]
+    pub fn chunks(&self, size: usize) -> impl Iterator<Item = Vec<(&K, &V)>> {
+        assert!(size != 0, "chunk size must be non-zero");
+
+        let mut iter = self.iter();
+
+        std::iter::from_fn(move || {
+            let chunk: Vec<_> = iter.by_ref().take(size).collect();
+
+            (!chunk.is_empty()).then_some(chunk)
+        })
+    }
+
+    /// Returns an iterator over every `step`-th entry in ascending key order, starting from the first one.
+    ///
+    /// A `step` up to about log2 of the length walks to the next sample in key order.
+    /// A larger one finds each sample from the root using subtree sizes instead of walking past the skipped entries,
+    /// so it runs in O(n / step * log n).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `step` is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 0..1000 {
+    ///     tree.insert(key, key * 2);
+    /// }
+    ///
+    /// for step in [1, 2, 7, 9, 10, 11, 100, 999, 1000, 5000] {
+    ///     assert!(tree.sample_every(step).eq(tree.iter().step_by(step)));
+    /// }
+    ///
+    /// // a step of 1 yields every entry, and a step larger than the length only the first one.
+    /// assert!(tree.sample_every(1).eq(tree.iter()));
+    /// assert!(tree.sample_every(1001).eq([(&0, &0)]));
+    ///
+    /// assert!(tree.sample_every(250).map(|(key, _)| *key).eq([0, 250, 500, 750]));
+    /// assert_eq!(RbTree::<i32, i32>::new().sample_every(3).count(), 0);
+    /// ```
+    #[track_caller]
+    pub fn sample_every(&self, step: usize) -> impl Iterator<Item = (&K, &V)> {
+        assert!(step != 0, "step must be non-zero");
+
+        let walk = step <= self.len.max(1).ilog2() as usize;
+        let mut next = self.first_node();
+        let mut index = 0;
+
+        std::iter::from_fn(move || {
+            let node = next?;
+            index += step;
+
+            next = match walk {
+                true => (0..step).try_fold(node, |cur, _| RbTree::next_node(cur)),
+                false => self.select_node(index),
+            };
+
+            Some(unsafe { node.key_value_ref() })
+        })
+    }
+
+    /// Returns an iterator over each pair of consecutive entries in ascending key order.
+    ///
+    /// It yields `len - 1` pairs, or nothing if the tree has fewer than two entries.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut events = RbTree::new();
+    /// for time in [10, 3, 7, 1] {
+    ///     events.insert(time, ());
+    /// }
+    ///
+    /// let gaps: Vec<_> = events.pairs().map(|((a, _), (b, _))| b - a).collect();
+    /// assert_eq!(gaps, [2, 4, 3]);
+    /// ```
+    pub fn pairs(&self) -> impl Iterator<Item = ((&K, &V), (&K, &V))> {
+        self.iter().zip(self.iter().skip(1))
+    }
+
+    /// Calls `f` on each entry in ascending key order, stopping at the first `Err` and returning it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 0..10 {
+    ///     tree.insert(key, key * 10);
+    /// }
+    ///
+    /// let mut visited = Vec::new();
+    /// let result = tree.try_for_each(|key, value| {
+    ///     if *key == 4 {
+    ///         return Err(format!("bad value {value}"));
+    ///     }
+    ///
+    ///     visited.push(*key);
+    ///     Ok(())
+    /// });
+    ///
+    /// assert_eq!(result, Err("bad value 40".to_string()));
+    /// assert_eq!(visited, [0, 1, 2, 3]);
+    /// ```
+    pub fn try_for_each<E, F>(&self, mut f: F) -> Result<(), E>
+    where
+        F: FnMut(&K, &V) -> Result<(), E>,
+    {
+        self.iter().try_for_each(|(key, value)| f(key, value))
+    }
+
+    /// Reduces entries into an accumulator in ascending key order, and returns the final accumulator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// tree.insert("b", 2);
+    /// tree.insert("c", 3);
+    /// tree.insert("a", 1);
+    ///
+    /// assert_eq!(tree.fold(0, |sum, _, value| sum + value), 6);
+    /// assert_eq!(tree.fold(String::new(), |keys, key, _| keys + key), "abc");
+    /// ```
+    pub fn fold<B, F>(&self, init: B, mut f: F) -> B
+    where
+        F: FnMut(B, &K, &V) -> B,
+    {
+        self.iter()
+            .fold(init, |acc, (key, value)| f(acc, key, value))
+    }
+
+    /// Applies `f` to every entry in ascending key order, giving mutable access to values only.
+    ///
+    /// It walks the tree with an explicit stack rather than `IterMut`, for hot bulk updates.
+    /// An augmented tree recomputes all of its augmented data afterwards, even if `f` panics.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut scores = RbTree::new();
+    /// scores.insert("a", 100.0);
+    /// scores.insert("b", 50.0);
+    ///
+    /// // decay every score each tick.
+    /// scores.adjust_all(|_, score| *score *= 0.5);
+    ///
+    /// assert_eq!(scores["a"], 50.0);
+    /// assert_eq!(scores["b"], 25.0);
+    /// ```
+    pub fn adjust_all<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V),
+    {
+        let _augment = AugmentOnDrop {
+            tree: self,
+            node: None,
+        };
+        let mut cur = self.root;
+        let mut stack = self.new_iter_stack();
+
+        while let Some((next, node)) = iter_next(cur, &mut stack) {
+            cur = next;
+
+            let (key, value) = unsafe { node.key_value_mut() };
+            f(key, value);
+        }
+    }
+
+    /// Threads an accumulator through mutable values in ascending key order, and returns the final accumulator.
+    ///
+    /// Like `adjust_all`, an augmented tree is recomputed afterwards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut series = RbTree::new();
+    /// for (time, amount) in [(3, 30), (1, 10), (4, 40), (2, 20)] {
+    ///     series.insert(time, amount);
+    /// }
+    ///
+    /// // in-place prefix sums
+    /// let total = series.reduce_values_mut(0, |sum, _, amount| {
+    ///     *amount += sum;
+    ///     *amount
+    /// });
+    ///
+    /// assert_eq!(total, 100);
+    /// assert_eq!(series.iter_mut().len(), 4);
+    /// assert!(series.iter_mut().map(|(k, v)| (*k, *v)).eq([(1, 10), (2, 30), (3, 60), (4, 100)]));
+    /// ```
+    pub fn reduce_values_mut<B, F>(&mut self, init: B, mut f: F) -> B
+    where
+        F: FnMut(B, &K, &mut V) -> B,
+    {
+        let _augment = AugmentOnDrop {
+            tree: self,
+            node: None,
+        };
+        let mut acc = init;
+        let mut cur = self.root;
+        let mut stack = self.new_iter_stack();
+
+        while let Some((next, node)) = iter_next(cur, &mut stack) {
+            cur = next;
+
+            let (key, value) = unsafe { node.key_value_mut() };
+            acc = f(acc, key, value);
+        }
+
+        acc
+    }
+
+    /// Check if tree has at least one node with key within `range`.
+    ///
+    /// It stops descending at the first node within `range`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// assert!(!tree.contains_range(..));
+    ///
+    /// for key in [10, 20, 30] {
+    ///     tree.insert(key, ());
+    /// }
+    ///
+    /// assert!(tree.contains_range(15..25));
+    /// assert!(tree.contains_range(..));
+    /// assert!(!tree.contains_range(21..30));
+    ///
+    /// // only the boundary key is within range.
+    /// assert!(tree.contains_range(21..=30));
+    /// assert!(tree.contains_range(..=10));
+    /// assert!(!tree.contains_range(..10));
+    /// ```
+    pub fn contains_range<Q, R>(&self, range: R) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let mut cur = self.root;
+
+        while !cur.is_nil() {
+            let key = cur.key().borrow();
+
+            if !is_after_start(key, &range) {
+                cur = cur.right.unwrap();
+            } else if !is_before_end(key, &range) {
+                cur = cur.left.unwrap();
+            } else {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Returns an iterator over key-value pairs within `range`, in ascending key order.
+    ///
+    /// Only the nodes on the paths to the range boundaries and the nodes within the range are visited.
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let (cur, stack, last) = self.range_state(&range);
+
+        RbTree::new_range(cur, stack, last)
+    }
+
+    /// Returns an iterator over key-value pairs comparing equal to the searched key under `cmp`,
+    /// in ascending key order, like `equal_range` of C++.
+    ///
+    /// `cmp` returns the ordering of the searched key relative to the given key, and must be consistent
+    /// with the key order of the tree. It can be coarser than the key order, so that distinct keys compare equal
+    /// and the iterator yields all of them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::cmp::Ordering;
+    /// use rbtree::RbTree;
+    ///
+    /// // ordered ignoring case first, so case variants of a string are adjacent.
+    /// #[derive(PartialEq, Eq)]
+    /// struct Name(&'static str);
+    ///
+    /// impl PartialOrd for Name {
+    ///     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    ///         Some(self.cmp(other))
+    ///     }
+    /// }
+    ///
+    /// impl Ord for Name {
+    ///     fn cmp(&self, other: &Self) -> Ordering {
+    ///         let lowercase = |name: &Name| name.0.to_lowercase();
+    ///         lowercase(self).cmp(&lowercase(other)).then(self.0.cmp(other.0))
+    ///     }
+    /// }
+    ///
+    /// let mut tree = RbTree::new();
+    /// for name in ["banana", "apple", "Cherry", "APPLE", "Apple", "Banana"] {
+    ///     tree.insert(Name(name), name.len());
+    /// }
+    ///
+    /// let ignore_case = |searched: &'static str| move |name: &Name| searched.cmp(&name.0.to_lowercase().as_str());
+    ///
+    /// let apples: Vec<_> = tree.equal_range_by(ignore_case("apple")).map(|(name, _)| name.0).collect();
+    /// assert_eq!(apples, ["APPLE", "Apple", "apple"]);
+    ///
+    /// let bananas = tree.equal_range_by(ignore_case("banana"));
+    /// assert_eq!(bananas.len(), 2);
+    /// assert!(bananas.rev().map(|(name, _)| name.0).eq(["banana", "Banana"]));
+    ///
+    /// assert_eq!(tree.equal_range_by(ignore_case("cherry")).count(), 1);
+    /// assert_eq!(tree.equal_range_by(ignore_case("durian")).count(), 0);
+    /// ```
+    pub fn equal_range_by<F>(&self, mut cmp: F) -> Range<'_, K, V>
+    where
+        F: FnMut(&K) -> Ordering,
+    {
+        let (cur, stack, last) = self.range_state_by(|key| cmp(key).reverse());
+
+        RbTree::new_range(cur, stack, last)
+    }
+
+    /// Returns an iterator over key-value pairs within `range`, in descending key order.
+    ///
+    /// It's the same as `range(range).rev()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut events = RbTree::new();
+    /// for time in 0..10 {
+    ///     events.insert(time, time * 10);
+    /// }
+    ///
+    /// assert!(events.range_rev(3..7).eq([(&6, &60), (&5, &50), (&4, &40), (&3, &30)]));
+    /// assert!(events.range_rev(3..7).eq(events.range(3..7).rev()));
+    /// assert!(events.range_rev(..).eq(events.iter().collect::<Vec<_>>().into_iter().rev()));
+    /// assert_eq!(events.range_rev(7..3).next(), None);
+    /// ```
+    pub fn range_rev<Q, R>(&self, range: R) -> std::iter::Rev<Range<'_, K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        self.range(range).rev()
+    }
+
+    /// Returns an iterator over key-value pairs whose keys start with `prefix`, in ascending key order.
+    ///
+    /// It's a `range` from `prefix` to the smallest string greater than every string starting with `prefix`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut words = RbTree::new();
+    /// for word in ["car", "card", "care", "cart", "cat", "dog", "ca"] {
+    ///     words.insert(word.to_string(), word.len());
+    /// }
+    ///
+    /// let keys = |prefix| words.prefix_range(prefix).map(|(key, _)| key.as_str()).collect::<Vec<_>>();
+    ///
+    /// assert_eq!(keys("car"), ["car", "card", "care", "cart"]);
+    /// assert_eq!(keys("ca"), ["ca", "car", "card", "care", "cart", "cat"]);
+    /// assert_eq!(keys("cart"), ["cart"]);
+    /// assert!(keys("x").is_empty());
+    /// assert_eq!(keys("").len(), words.len());
+    /// ```
+    pub fn prefix_range(&self, prefix: &str) -> Range<'_, K, V>
+    where
+        K: Borrow<str>,
+    {
+        // drop trailing chars which have no greater char, and increment the last remaining char
+        let mut end = prefix.trim_end_matches(char::MAX).to_string();
+        let upper = end.pop().map(|last| {
+            let next = (last as u32 + 1..=char::MAX as u32)
+                .find_map(char::from_u32)
+                .unwrap();
+            end.push(next);
+
+            end
+        });
+
+        let end_bound = match &upper {
+            Some(upper) => Bound::Excluded(upper.as_str()),
+            None => Bound::Unbounded,
+        };
+
+        self.range::<str, _>((Bound::Included(prefix), end_bound))
+    }
+
+    /// Clones the entries within `range` into a new tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 0..100 {
+    ///     tree.insert(key, key.to_string());
+    /// }
+    ///
+    /// let window = tree.range_to_tree(20..=40);
+    ///
+    /// assert!(window.iter().map(|(key, _)| key).eq(tree.range(20..=40).map(|(key, _)| key)));
+    /// assert_eq!(window[&30], "30");
+    /// assert!(window.is_correct_rb_tree());
+    /// ```
+    pub fn range_to_tree<Q, R>(&self, range: R) -> RbTree<K, V, A>
+    where
+        K: Borrow<Q> + Clone,
+        V: Clone,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let mut tree = Self::empty();
+        tree.fill_sorted(
+            self.range(range)
+                .map(|(key, value)| (key.clone(), value.clone())),
+        );
+
+        tree
+    }
+
+    /// Removes all entries within `range`, and returns an iterator over the removed key-value pairs in ascending key order.
+    ///
+    /// The entries are removed one by one as the iterator advances, and the rest are removed when it's dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 0..10 {
+    ///     tree.insert(key, key * 10);
+    /// }
+    ///
+    /// let drained: Vec<_> = tree.drain_range(3..7).collect();
+    ///
+    /// assert_eq!(drained, [(3, 30), (4, 40), (5, 50), (6, 60)]);
+    /// assert!(tree.iter().map(|(key, _)| *key).eq([0, 1, 2, 7, 8, 9]));
+    /// assert!(tree.is_correct_rb_tree());
+    /// ```
+    pub fn drain_range<Q, R>(&mut self, range: R) -> DrainRange<'_, K, V, A>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let (next, remaining) = self.range_first_and_len(&range);
+
+        DrainRange {
+            tree: self,
+            next,
+            remaining,
+        }
+    }
+
+    /// Returns the number of entries within `range`.
+    ///
+    /// It takes O(log n) using subtree sizes, without visiting the entries.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 0..100 {
+    ///     tree.insert(key, ());
+    /// }
+    ///
+    /// assert_eq!(tree.count_range(10..20), 10);
+    /// assert_eq!(tree.count_range(90..), 10);
+    /// assert_eq!(tree.count_range(..), 100);
+    /// assert_eq!(tree.count_range(200..), 0);
+    /// ```
+    pub fn count_range<Q, R>(&self, range: R) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        self.range_first_and_len(&range).1
+    }
+
+    /// Removes all entries within `range`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// for range in [(0, 0), (0, 10), (3, 7), (9, 20), (20, 30)] {
+    ///     let mut tree = RbTree::new();
+    ///     for key in 0..10 {
+    ///         tree.insert(key, ());
+    ///     }
+    ///
+    ///     tree.clear_range(range.0..range.1);
+    ///
+    ///     assert_eq!(tree.len(), tree.iter().count());
+    ///     assert!(tree.iter().all(|(key, _)| !(range.0..range.1).contains(key)));
+    ///     assert!(tree.is_correct_rb_tree());
+    /// }
+    /// ```
+    pub fn clear_range<Q, R>(&mut self, range: R)
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        self.drain_range(range).for_each(drop);
+
+        // subtree sizes are maintained apart from `len`, so they're an independent count.
+        debug_assert_eq!(self.len, self.root.size);
+    }
+
+    /// Returns the number of entries within `range` with the first of them, or `None` if no entry is within `range`.
+    ///
+    /// It takes a single descent using subtree sizes, which forks at the highest entry within `range`
+    /// into one path to each end of `range`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 0..100 {
+    ///     tree.insert(key, key * 10);
+    /// }
+    ///
+    /// assert_eq!(tree.range_summary(25..50), Some((25, &25, &250)));
+    /// assert_eq!(tree.range_summary(..), Some((100, &0, &0)));
+    /// assert_eq!(tree.range_summary(100..), None);
+    /// assert_eq!(RbTree::<i32, i32>::new().range_summary(..), None);
+    ///
+    /// for range in [(0, 1), (10, 11), (3, 97), (50, 100), (40, 40), (99, 200)] {
+    ///     let summary = tree.range_summary(range.0..range.1);
+    ///     let first = tree.range(range.0..range.1).next();
+    ///
+    ///     assert_eq!(summary.map(|(len, _, _)| len), first.map(|_| tree.count_range(range.0..range.1)));
+    ///     assert_eq!(summary.map(|(_, key, value)| (key, value)), first);
+    /// }
+    /// ```
+    pub fn range_summary<Q, R>(&self, range: R) -> Option<(usize, &K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let mut top = self.root;
+
+        // the highest node within `range`, whose subtrees hold the rest of the range.
+        while !top.is_nil() {
+            let key = top.key().borrow();
+
+            top = match (is_after_start(key, &range), is_before_end(key, &range)) {
+                (false, _) => top.right.unwrap(),
+                (true, false) => top.left.unwrap(),
+                (true, true) => break,
+            };
+        }
+
+        if top.is_nil() {
+            return None;
+        }
+
+        let mut len = 1;
+        let mut first = top;
+
+        // keys in the left subtree are all before the end.
+        let mut cur = top.left.unwrap();
+        while !cur.is_nil() {
+            if is_after_start(cur.key().borrow(), &range) {
+                len += cur.right.unwrap().size + 1;
+                first = cur;
+                cur = cur.left.unwrap();
+            } else {
+                cur = cur.right.unwrap();
+            }
+        }
+
+        // keys in the right subtree are all after the start.
+        let mut cur = top.right.unwrap();
+        while !cur.is_nil() {
+            if is_before_end(cur.key().borrow(), &range) {
+                len += cur.left.unwrap().size + 1;
+                cur = cur.right.unwrap();
+            } else {
+                cur = cur.left.unwrap();
+            }
+        }
+
+        let (key, value) = unsafe { first.key_value_ref() };
+
+        Some((len, key, value))
+    }
+
+    /// Removes all entries within `range`, and returns them in ascending key order.
+    ///
+    /// The returned `Vec` is allocated once with the exact capacity, counted like `count_range`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in [5, 1, 8, 3, 9, 2, 7] {
+    ///     tree.insert(key, key * 10);
+    /// }
+    ///
+    /// let removed = tree.remove_range_collect(2..=7);
+    ///
+    /// assert_eq!(removed, [(2, 20), (3, 30), (5, 50), (7, 70)]);
+    /// assert_eq!(removed.capacity(), 4);
+    /// assert!(tree.iter().map(|(key, _)| *key).eq([1, 8, 9]));
+    /// assert!(tree.is_correct_rb_tree());
+    /// ```
+    pub fn remove_range_collect<Q, R>(&mut self, range: R) -> Vec<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let mut removed = Vec::with_capacity(self.range_first_and_len(&range).1);
+        removed.extend(self.drain_range(range));
+
+        removed
+    }
+
+    /// Returns an iterator over keys within `range`, in ascending key order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 0..10 {
+    ///     tree.insert(key, key * 10);
+    /// }
+    ///
+    /// assert!(tree.keys_range(3..6).eq([&3, &4, &5]));
+    /// assert!(tree.keys_range(3..6).eq(tree.range(3..6).map(|(key, _)| key)));
+    /// assert!(tree.values_range(3..6).eq([&30, &40, &50]));
+    /// assert!(tree.values_range(3..6).eq(tree.range(3..6).map(|(_, value)| value)));
+    /// ```
+    pub fn keys_range<Q, R>(&self, range: R) -> impl Iterator<Item = &K>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        self.range(range).map(|(key, _)| key)
+    }
+
+    /// Returns an iterator over values whose keys are within `range`, in ascending key order.
+    pub fn values_range<Q, R>(&self, range: R) -> impl Iterator<Item = &V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        self.range(range).map(|(_, value)| value)
+    }
+
+    /// Returns the total length of the value collections whose keys are within `range`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut events_by_hour = RbTree::new();
+    /// events_by_hour.insert(9, vec!["standup"]);
+    /// events_by_hour.insert(10, vec!["review", "lunch order"]);
+    /// events_by_hour.insert(11, vec![]);
+    /// events_by_hour.insert(12, vec!["lunch", "walk", "call"]);
+    ///
+    /// assert_eq!(events_by_hour.total_values_in_range(10..=12), 5);
+    /// assert_eq!(events_by_hour.total_values_in_range(..), 6);
+    /// assert_eq!(events_by_hour.total_values_in_range(13..), 0);
+    /// ```
+    pub fn total_values_in_range<T, Q, R>(&self, range: R) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+        V: AsRef<[T]>,
+    {
+        self.values_range(range)
+            .map(|value| value.as_ref().len())
+            .sum()
+    }
+
+    /// find the first node within `range`, and the number of nodes within `range`.
+    fn range_first_and_len<Q, R>(&self, range: &R) -> (Option<RbNode<K, V>>, usize)
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let (_, stack, last) = self.range_state(range);
+
+        match (stack.last(), last) {
+            (Some(&first), Some(last)) => (
+                Some(first),
+                RbTree::node_index(last) - RbTree::node_index(first) + 1,
+            ),
+            _ => (None, 0),
+        }
+    }
+
+    /// find the traversal state of the first node within `range`, and the last node within `range`.
+    ///
+    /// The last node is `None` if there's no node within `range`.
+    #[allow(clippy::type_complexity)]
+    fn range_state<Q, R>(
+        &self,
+        range: &R,
+    ) -> (RbNode<K, V>, Vec<RbNode<K, V>>, Option<RbNode<K, V>>)
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        self.range_state_by(|key| {
+            let key = key.borrow();
+
+            match (is_after_start(key, range), is_before_end(key, range)) {
+                (false, _) => Ordering::Less,
+                (true, false) => Ordering::Greater,
+                (true, true) => Ordering::Equal,
+            }
+        })
+    }
+
+    /// find the traversal state like `range_state`, for the range of keys for which `position` returns `Equal`.
+    ///
+    /// `position` returns the ordering of the given key relative to the range, and must be consistent with the key order.
+    #[allow(clippy::type_complexity)]
+    fn range_state_by<F>(
+        &self,
+        mut position: F,
+    ) -> (RbNode<K, V>, Vec<RbNode<K, V>>, Option<RbNode<K, V>>)
+    where
+        F: FnMut(&K) -> Ordering,
+    {
+        let mut stack = self.new_iter_stack();
+        let mut cur = self.root;
+
+        while !cur.is_nil() {
+            if position(cur.key()) != Ordering::Less {
+                stack.push(cur);
+                cur = cur.left.unwrap();
+            } else {
+                cur = cur.right.unwrap();
+            }
+        }
+
+        let mut last = None;
+        let mut last_cur = self.root;
+
+        while !last_cur.is_nil() {
+            if position(last_cur.key()) != Ordering::Greater {
+                last = Some(last_cur);
+                last_cur = last_cur.right.unwrap();
+            } else {
+                last_cur = last_cur.left.unwrap();
+            }
+        }
+
+        // first node is after last node when no node is within the range
+        let last = match stack.last() {
+            Some(&first) if position(first.key()) != Ordering::Greater => last,
+            _ => None,
+        };
+
+        (cur, stack, last)
+    }
+
+    /// Does nothing, since nodes are allocated one by one on insertion.
+    ///
+    /// It exists for parity with collections which can reserve capacity.
+    pub fn reserve(&mut self, _additional: usize) {}
+
+    /// Always returns `Ok(())`, since nodes are allocated one by one on insertion.
+    ///
+    /// It exists for parity with collections which can reserve capacity.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree: RbTree<i32, i32> = RbTree::new();
+    ///
+    /// tree.reserve(10);
+    /// assert!(tree.try_reserve(10).is_ok());
+    /// ```
+    pub fn try_reserve(&mut self, _additional: usize) -> Result<(), TryReserveError> {
+        Ok(())
+    }
+
+    /// Makes the tree empty.
+    ///
+    /// root node turns into Nil node.
+    pub fn clear(&mut self) {
+        self.invalidate_lookup_cache();
+
+        if self.root.is_nil() {
+            return;
+        }
+
+        let mut stack = vec![self.root];
+
+        while let Some(mut cur) = stack.pop() {
+            let (left_is_nil, right_is_nil) =
+                (cur.left.unwrap().is_nil(), cur.right.unwrap().is_nil());
+
+            if left_is_nil && right_is_nil {
+                cur.uninit(&mut self.pool);
+            } else {
+                stack.push(cur);
+
+                if !right_is_nil {
+                    stack.push(cur.right.unwrap());
+                }
+
+                if !left_is_nil {
+                    stack.push(cur.left.unwrap());
+                }
+            }
+        }
+
+        self.len = 0;
+    }
+
+    /// Returns the smallest key in the tree, or `None` if the tree is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// assert_eq!(tree.min(), None);
+    ///
+    /// tree.insert(1, "a");
+    /// assert_eq!(tree.min(), Some(&1));
+    ///
+    /// tree.insert(0, "b");
+    /// assert_eq!(tree.min(), Some(&0));
+    /// ```
+    pub fn min(&self) -> Option<&K> {
+        let target = RbTree::min_node(self.root);
+
+        match target.is_nil() {
+            true => None,
+            false => Some(unsafe { target.key_value_ref().0 }),
+        }
+    }
+
+    /// Returns the largest key in the tree, or `None` if the tree is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// assert_eq!(tree.max(), None);
+    ///
+    /// tree.insert(1, "a");
+    /// assert_eq!(tree.max(), Some(&1));
+    ///
+    /// tree.insert(2, "b");
+    /// assert_eq!(tree.max(), Some(&2));
+    /// ```
+    pub fn max(&self) -> Option<&K> {
+        let target = RbTree::max_node(self.root);
+
+        match target.is_nil() {
+            true => None,
+            false => Some(unsafe { target.key_value_ref().0 }),
+        }
+    }
+
+    /// Returns the smallest and the largest keys in the tree, or `None` if the tree is empty.
+    ///
+    /// This is an alias of `extremes`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut readings = RbTree::new();
+    /// assert_eq!(readings.bounds(), None);
+    ///
+    /// readings.insert(1200, 20.5);
+    /// assert_eq!(readings.bounds(), Some((&1200, &1200)));
+    ///
+    /// for time in [900, 1500, 1000] {
+    ///     readings.insert(time, 21.0);
+    /// }
+    /// assert_eq!(readings.bounds(), Some((&900, &1500)));
+    /// ```
+    pub fn bounds(&self) -> Option<(&K, &K)> {
+        self.extremes()
+    }
+
+    /// Returns the first key in the tree, or `None` if the tree is empty.
+    ///
+    /// It is same to `min`, named after `first_key_value` of `BTreeMap`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// assert_eq!(tree.first_key(), None);
+    ///
+    /// tree.insert(2, "b");
+    /// tree.insert(1, "a");
+    /// assert_eq!(tree.first_key(), Some(&1));
+    /// ```
+    pub fn first_key(&self) -> Option<&K> {
+        self.min()
+    }
+
+    /// Returns the last key in the tree, or `None` if the tree is empty.
+    ///
+    /// It is same to `max`, named after `last_key_value` of `BTreeMap`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// assert_eq!(tree.last_key(), None);
+    ///
+    /// tree.insert(1, "a");
+    /// tree.insert(2, "b");
+    /// assert_eq!(tree.last_key(), Some(&2));
+    /// ```
+    pub fn last_key(&self) -> Option<&K> {
+        self.max()
+    }
+
+    /// Returns both the smallest and the largest keys, or `None` if the tree is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// assert_eq!(tree.extremes(), None);
+    ///
+    /// tree.insert(1, "a");
+    /// assert_eq!(tree.extremes(), Some((&1, &1)));
+    ///
+    /// tree.insert(3, "c");
+    /// tree.insert(2, "b");
+    /// assert_eq!(tree.extremes(), Some((&1, &3)));
+    /// ```
+    pub fn extremes(&self) -> Option<(&K, &K)> {
+        if self.is_empty() {
+            return None;
+        }
+
+        Some((self.min()?, self.max()?))
+    }
+
+    /// Retains only the entries for which `f` returns `true`, visiting entries in ascending key order.
+    ///
+    /// If `f` panics, the entries visited before are already removed or kept, and the rest are kept.
+    /// The tree stays a valid RB-Tree with the correct length, and every removed entry is dropped once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::panic::{self, AssertUnwindSafe};
+    /// use std::rc::Rc;
+    /// use rbtree::RbTree;
+    ///
+    /// let counter = Rc::new(());
+    /// let mut tree = RbTree::new();
+    /// for key in 0..100 {
+    ///     tree.insert(key, Rc::clone(&counter));
+    /// }
+    ///
+    /// let result = panic::catch_unwind(AssertUnwindSafe(|| {
+    ///     tree.retain(|key, _| {
+    ///         if *key == 60 {
+    ///             panic!("unexpected key");
+    ///         }
+    ///
+    ///         key % 2 == 0
+    ///     });
+    /// }));
+    /// assert!(result.is_err());
+    ///
+    /// // odd keys before 60 are removed, and the rest are untouched.
+    /// let expected: Vec<_> = (0..60).step_by(2).chain(60..100).collect();
+    /// assert!(tree.iter().map(|(key, _)| *key).eq(expected.iter().copied()));
+    /// assert_eq!(tree.len(), expected.len());
+    /// assert!(tree.is_correct_rb_tree());
+    /// assert_eq!(Rc::strong_count(&counter), tree.len() + 1);
+    ///
+    /// drop(tree);
+    /// assert_eq!(Rc::strong_count(&counter), 1);
+    /// ```
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let first = self.first_node();
+        self.retain_nodes(first, self.len, f, drop);
+    }
+
+    /// Removes every key present in `other` from `self`.
+    ///
+    /// The values of `other` are never read, so it may be a tree of any value type.
+    /// It takes O(n + m) comparisons for trees of n and m entries.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::from_sorted_iter((0..10).map(|key| (key, key * 10)));
+    ///
+    /// // overlapping keys, including ones not in `tree`.
+    /// let other = RbTree::from_sorted_iter((5..15).map(|key| (key, "removed")));
+    /// tree.difference_update(&other);
+    /// assert!(tree.iter().eq([(&0, &0), (&1, &10), (&2, &20), (&3, &30), (&4, &40)]));
+    /// assert!(tree.is_correct_rb_tree());
+    ///
+    /// // disjoint keys remove nothing.
+    /// let other = RbTree::from_sorted_iter((20..30).map(|key| (key, 0)));
+    /// tree.difference_update(&other);
+    /// assert_eq!(tree.len(), 5);
+    ///
+    /// let mut evens = RbTree::from_sorted_iter((0..1000).map(|key| (key, ())));
+    /// let odds = RbTree::from_sorted_iter((0..1000).filter(|key| key % 2 == 1).map(|key| (key, ())));
+    /// evens.difference_update(&odds);
+    /// assert!(evens.iter().map(|(key, _)| *key).eq((0..1000).step_by(2)));
+    /// assert!(evens.is_correct_rb_tree());
+    /// ```
+    pub fn difference_update<V2, A2>(&mut self, other: &RbTree<K, V2, A2>)
+    where
+        K: Ord,
+        A2: Augment<K, V2>,
+    {
+        let mut other_keys = other.iter().map(|(key, _)| key).peekable();
+
+        self.retain(|key, _| {
+            while other_keys.next_if(|other_key| *other_key < key).is_some() {}
+
+            other_keys.peek() != Some(&key)
+        });
+    }
+
+    /// Removes every key not present in `other` from `self`, keeping the values of `self`.
+    ///
+    /// `other` only decides which keys remain, so its value type may differ from the one of `self`.
+    /// The removed entries are dropped in ascending key order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::from_sorted_iter((0..10).map(|key| (key, key * 10)));
+    ///
+    /// let other = RbTree::from_sorted_iter((5..15).map(|key| (key, key.to_string())));
+    /// tree.intersection_update(&other);
+    /// assert!(tree.iter().eq([(&5, &50), (&6, &60), (&7, &70), (&8, &80), (&9, &90)]));
+    /// assert!(tree.is_correct_rb_tree());
+    ///
+    /// let mut multiples_of_2 = RbTree::from_sorted_iter((0..3000).step_by(2).map(|key| (key, ())));
+    /// let multiples_of_3 = RbTree::from_sorted_iter((0..3000).step_by(3).map(|key| (key, ())));
+    /// multiples_of_2.intersection_update(&multiples_of_3);
+    /// assert!(multiples_of_2.iter().map(|(key, _)| *key).eq((0..3000).step_by(6)));
+    /// assert!(multiples_of_2.is_correct_rb_tree());
+    ///
+    /// // disjoint keys remove everything.
+    /// multiples_of_2.intersection_update(&RbTree::from_sorted_iter([(1, ())]));
+    /// assert!(multiples_of_2.is_empty());
+    /// ```
+    pub fn intersection_update<V2, A2>(&mut self, other: &RbTree<K, V2, A2>)
+    where
+        K: Ord,
+        A2: Augment<K, V2>,
+    {
+        let mut other_keys = other.iter().map(|(key, _)| key).peekable();
+
+        self.retain(|key, _| {
+            while other_keys.next_if(|other_key| *other_key < key).is_some() {}
+
+            other_keys.peek() == Some(&key)
+        });
+    }
+
+    /// Returns an iterator over keys in exactly one of `self` and `other`, in ascending key order.
+    ///
+    /// The iterator is lazy, skipping the keys common to both trees as it goes.
+    /// Only keys are yielded, so `other` may hold a different value type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let a = RbTree::from_sorted_iter([1, 2, 3, 5, 8].map(|key| (key, ())));
+    /// let b = RbTree::from_sorted_iter([2, 3, 4, 8, 9, 10].map(|key| (key, key * 10)));
+    ///
+    /// assert!(a.symmetric_difference(&b).eq(&[1, 4, 5, 9, 10]));
+    /// assert!(b.symmetric_difference(&a).eq(&[1, 4, 5, 9, 10]));
+    /// assert_eq!(a.symmetric_difference(&a).count(), 0);
+    /// assert!(a.symmetric_difference(&RbTree::<i32, ()>::new()).eq(&[1, 2, 3, 5, 8]));
+    /// ```
+    pub fn symmetric_difference<'a, V2, A2>(
+        &'a self,
+        other: &'a RbTree<K, V2, A2>,
+    ) -> impl Iterator<Item = &'a K>
+    where
+        K: Ord,
+        A2: Augment<K, V2>,
+    {
+        let mut left = self.iter().map(|(key, _)| key).peekable();
+        let mut right = other.iter().map(|(key, _)| key).peekable();
+
+        std::iter::from_fn(move || loop {
+            let ordering = match (left.peek(), right.peek()) {
+                (Some(left), Some(right)) => left.cmp(right),
+                (Some(_), None) => Ordering::Less,
+                (None, _) => Ordering::Greater,
+            };
+
+            match ordering {
+                Ordering::Less => return left.next(),
+                Ordering::Greater => return right.next(),
+                Ordering::Equal => {
+                    left.next();
+                    right.next();
+                }
+            }
+        })
+    }
+
+    /// Returns `true` if every key of `self` is in `other`, whatever the value types of both trees.
+    ///
+    /// It returns `false` without comparing any key if `self` has more entries than `other`,
+    /// and otherwise at the first key of `self` missing in `other`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let tree = |keys: &[i32]| RbTree::from_sorted_iter(keys.iter().map(|key| (*key, ())));
+    ///
+    /// let small = tree(&[2, 4]);
+    /// let large = tree(&[1, 2, 3, 4, 5]);
+    ///
+    /// assert!(small.is_subset(&large));
+    /// assert!(!large.is_subset(&small));
+    /// assert!(large.is_subset(&tree(&[1, 2, 3, 4, 5])));
+    /// assert!(!tree(&[2, 6]).is_subset(&large));
+    /// assert!(tree(&[]).is_subset(&small));
+    ///
+    /// let names = RbTree::from_sorted_iter([(2, "two"), (4, "four")]);
+    /// assert!(small.is_subset(&names));
+    /// ```
+    pub fn is_subset<V2, A2>(&self, other: &RbTree<K, V2, A2>) -> bool
+    where
+        K: Ord,
+        A2: Augment<K, V2>,
+    {
+        if self.len > other.len {
+            return false;
+        }
+
+        let mut other_keys = other.iter().map(|(key, _)| key).peekable();
+
+        self.iter().all(|(key, _)| {
+            while other_keys.next_if(|other_key| *other_key < key).is_some() {}
+
+            other_keys.next_if_eq(&key).is_some()
+        })
+    }
+
+    /// Returns `true` if every key of `other` is in `self`.
+    ///
+    /// This is `other.is_subset(self)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let tree = |keys: &[i32]| RbTree::from_sorted_iter(keys.iter().map(|key| (*key, ())));
+    ///
+    /// assert!(tree(&[1, 2, 3]).is_superset(&tree(&[1, 3])));
+    /// assert!(tree(&[1, 2, 3]).is_superset(&tree(&[1, 2, 3])));
+    /// assert!(!tree(&[1, 2, 3]).is_superset(&tree(&[3, 4])));
+    /// ```
+    pub fn is_superset<V2, A2>(&self, other: &RbTree<K, V2, A2>) -> bool
+    where
+        K: Ord,
+        A2: Augment<K, V2>,
+    {
+        other.is_subset(self)
+    }
+
+    /// Returns `true` if `self` and `other` have no key in common.
+    ///
+    /// It stops at the first common key, and the trees may hold different value types.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let tree = |keys: &[i32]| RbTree::from_sorted_iter(keys.iter().map(|key| (*key, ())));
+    ///
+    /// assert!(tree(&[1, 3, 5]).is_disjoint(&tree(&[2, 4, 6])));
+    /// assert!(!tree(&[1, 3, 5]).is_disjoint(&tree(&[4, 5])));
+    /// assert!(!tree(&[1, 2]).is_disjoint(&tree(&[1, 2])));
+    /// assert!(tree(&[1, 2]).is_disjoint(&tree(&[])));
+    /// assert!(!tree(&[1, 2]).is_disjoint(&RbTree::from_sorted_iter([(2, 'b')])));
+    /// ```
+    pub fn is_disjoint<V2, A2>(&self, other: &RbTree<K, V2, A2>) -> bool
+    where
+        K: Ord,
+        A2: Augment<K, V2>,
+    {
+        let mut other_keys = other.iter().map(|(key, _)| key).peekable();
+
+        self.iter().all(|(key, _)| {
+            while other_keys.next_if(|other_key| *other_key < key).is_some() {}
+
+            other_keys.peek() != Some(&key)
+        })
+    }
+
+    /// Retains only the entries for which `f` returns `true`, and returns the removed key-value pairs
+    /// in ascending key order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 0..10 {
+    ///     tree.insert(key, key * 10);
+    /// }
+    ///
+    /// let removed = tree.retain_with_removed(|key, _| key % 2 == 0);
+    ///
+    /// assert_eq!(removed, [(1, 10), (3, 30), (5, 50), (7, 70), (9, 90)]);
+    /// assert!(tree.iter().map(|(key, _)| *key).eq([0, 2, 4, 6, 8]));
+    /// assert!(tree.is_correct_rb_tree());
+    /// ```
+    pub fn retain_with_removed<F>(&mut self, f: F) -> Vec<(K, V)>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let mut removed = Vec::new();
+
+        let first = self.first_node();
+        self.retain_nodes(first, self.len, f, |entry| removed.push(entry));
+
+        removed
+    }
+
+    /// Retains only the entries within `range` for which `f` returns `true`.
+    ///
+    /// Entries outside `range` are kept without being visited.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 0..10 {
+    ///     tree.insert(key, key * 10);
+    /// }
+    ///
+    /// tree.retain_range(3..7, |key, _| key % 2 == 0);
+    ///
+    /// assert!(tree.iter().map(|(key, _)| *key).eq([0, 1, 2, 4, 6, 7, 8, 9]));
+    /// assert!(tree.is_correct_rb_tree());
+    /// ```
+    pub fn retain_range<Q, R, F>(&mut self, range: R, f: F)
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let (first, len) = self.range_first_and_len(&range);
+        self.retain_nodes(first, len, f, drop);
+    }
+
+    /// Visits `len` entries from `first` in ascending key order,
+    /// and removes the entries for which `f` returns `false`, passing them to `removed`.
+    fn retain_nodes<F, G>(
+        &mut self,
+        first: Option<RbNode<K, V>>,
+        len: usize,
+        mut f: F,
+        mut removed: G,
+    ) where
+        F: FnMut(&K, &mut V) -> bool,
+        G: FnMut((K, V)),
+    {
+        let mut cur = first;
+
+        for _ in 0..len {
+            let node = match cur {
+                Some(node) => node,
+                None => break,
+            };
+
+            let (key, value) = unsafe { node.key_value_mut() };
+            let keep = {
+                // `f` may change the value, even if it panics.
+                let _augment = AugmentOnDrop {
+                    tree: self,
+                    node: Some(node),
+                };
+                f(key, value)
+            };
+
+            cur = match keep {
+                true => RbTree::next_node(node),
+                false => {
+                    let (removed_key_value, next) = self.remove_node(node);
+                    removed(removed_key_value);
+
+                    next
+                }
+            };
+        }
+    }
+
+    /// Retains only the entries for which `f` returns `true` with the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 0..10 {
+    ///     tree.insert(key, key * 10);
+    /// }
+    ///
+    /// tree.retain_keys(|key| key % 3 == 0);
+    ///
+    /// assert!(tree.iter().eq([(&0, &0), (&3, &30), (&6, &60), (&9, &90)]));
+    /// assert!(tree.is_correct_rb_tree());
+    /// ```
+    pub fn retain_keys<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K) -> bool,
+    {
+        self.retain(|key, _| f(key));
+    }
+
+    /// Removes every entry whose value is equal to the value of the previous entry in key order,
+    /// so only the first entry of each run of equal values remains.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// // states of a device sampled over time.
+    /// let mut states = RbTree::new();
+    /// for (time, state) in [(0, "off"), (1, "off"), (2, "on"), (3, "on"), (4, "on"), (5, "off"), (6, "on"), (7, "on")] {
+    ///     states.insert(time, state);
+    /// }
+    ///
+    /// states.dedup_consecutive_values();
+    ///
+    /// // only the transitions remain.
+    /// assert!(states.iter().eq([(&0, &"off"), (&2, &"on"), (&5, &"off"), (&6, &"on")]));
+    /// assert!(states.is_correct_rb_tree());
+    /// ```
+    pub fn dedup_consecutive_values(&mut self)
+    where
+        V: PartialEq,
+    {
+        self.coalesce(|_, left, _, right| left == right);
+    }
+
+    /// Walks consecutive entries in key order, and merges each right entry into the left one
+    /// if `f` returns `true` for them.
+    ///
+    /// `f` is called with the left entry, whose value it may combine the right value into, and the right entry.
+    /// If it returns `true`, the right entry is removed and the left one is compared with the next entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// // integer ranges as start => end, inclusive.
+    /// let mut ranges = RbTree::new();
+    /// for (start, end) in [(1, 3), (4, 6), (5, 8), (10, 12), (11, 11), (14, 15), (20, 21)] {
+    ///     ranges.insert(start, end);
+    /// }
+    ///
+    /// // merge overlapping or adjacent ranges.
+    /// ranges.coalesce(|_, left_end, right_start, right_end| {
+    ///     if *right_start > *left_end + 1 {
+    ///         return false;
+    ///     }
+    ///
+    ///     *left_end = (*left_end).max(*right_end);
+    ///     true
+    /// });
+    ///
+    /// assert!(ranges.iter().eq([(&1, &8), (&10, &12), (&14, &15), (&20, &21)]));
+    /// assert!(ranges.is_correct_rb_tree());
+    /// ```
+    pub fn coalesce<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V, &K, &V) -> bool,
+    {
+        let Some(mut left) = self.first_node() else {
+            return;
+        };
+
+        let mut cur = RbTree::next_node(left);
+
+        while let Some(right) = cur {
+            let (left_key, left_value) = unsafe { left.key_value_mut() };
+            let (right_key, right_value) = unsafe { right.key_value_ref() };
+            let merge = {
+                let _augment = AugmentOnDrop {
+                    tree: self,
+                    node: Some(left),
+                };
+                f(left_key, left_value, right_key, right_value)
+            };
+
+            if merge {
+                // the successor of `right` may be moved into `right`, but `left` stays.
+                let (_, next) = self.remove_node(right);
+                cur = next;
+            } else {
+                left = right;
+                cur = RbTree::next_node(right);
+            }
+        }
+    }
+
+    /// Consumes the tree and returns its key-value pairs in ascending key order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::rc::Rc;
+    /// use rbtree::RbTree;
+    ///
+    /// let counter = Rc::new(());
+    /// let mut tree = RbTree::new();
+    /// for key in [3, 1, 2] {
+    ///     tree.insert(key, Rc::clone(&counter));
+    /// }
+    ///
+    /// let vec = tree.into_sorted_vec();
+    /// assert_eq!(vec.len(), 3);
+    /// assert!(vec.iter().map(|(key, _)| *key).eq([1, 2, 3]));
+    ///
+    /// // every value is dropped exactly once.
+    /// drop(vec);
+    /// assert_eq!(Rc::strong_count(&counter), 1);
+    /// ```
+    pub fn into_sorted_vec(self) -> Vec<(K, V)> {
+        let mut vec = Vec::with_capacity(self.len);
+        vec.extend(self);
+
+        vec
+    }
+
+    /// Consumes the tree and returns an iterator over its key-value pairs in descending key order.
+    ///
+    /// It's the same as `into_iter().rev()`. The pairs which are not yielded are dropped with the iterator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::rc::Rc;
+    /// use rbtree::RbTree;
+    ///
+    /// let counter = Rc::new(());
+    /// let mut tree = RbTree::new();
+    /// for key in 0..10 {
+    ///     tree.insert(key, Rc::clone(&counter));
+    /// }
+    ///
+    /// let mut iter = tree.into_iter_rev();
+    /// let largest: Vec<_> = iter.by_ref().take(3).map(|(key, _)| key).collect();
+    /// assert_eq!(largest, [9, 8, 7]);
+    /// assert_eq!(Rc::strong_count(&counter), 8);
+    ///
+    /// // the rest are dropped exactly once with the iterator.
+    /// drop(iter);
+    /// assert_eq!(Rc::strong_count(&counter), 1);
+    ///
+    /// // both ends meet without yielding an entry twice.
+    /// let tree = RbTree::from_sorted_iter((0..5).map(|key| (key, ())));
+    /// let mut iter = tree.into_iter();
+    /// assert_eq!(iter.next_back(), Some((4, ())));
+    /// assert_eq!(iter.next(), Some((0, ())));
+    /// assert_eq!(iter.len(), 3);
+    /// assert!(iter.map(|(key, _)| key).eq([1, 2, 3]));
+    /// ```
+    pub fn into_iter_rev(self) -> std::iter::Rev<IntoIter<K, V, A>> {
+        self.into_iter().rev()
+    }
+
+    /// Returns references to all key-value pairs in ascending key order, allocated once with the exact capacity.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in [3, 1, 2] {
+    ///     tree.insert(key, key * 10);
+    /// }
+    ///
+    /// {
+    ///     let refs = tree.to_vec_refs();
+    ///     assert_eq!(refs, [(&1, &10), (&2, &20), (&3, &30)]);
+    ///     assert_eq!(refs.capacity(), 3);
+    /// }
+    ///
+    /// // the borrow has ended, so the tree can be mutated again.
+    /// *tree.get_mut(&1).unwrap() += 1;
+    /// assert_eq!(tree[&1], 11);
+    /// ```
+    pub fn to_vec_refs(&self) -> Vec<(&K, &V)> {
+        let mut vec = Vec::with_capacity(self.len);
+        vec.extend(self.iter());
+
+        vec
+    }
+
+    /// Clears `buf` and fills it with clones of all keys in ascending order, reusing its allocation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in [3, 1, 2] {
+    ///     tree.insert(key, ());
+    /// }
+    ///
+    /// let mut buf = vec![42, 42, 42, 42, 42];
+    /// tree.collect_keys_into(&mut buf);
+    /// assert_eq!(buf, [1, 2, 3]);
+    ///
+    /// tree.remove(&2);
+    /// tree.collect_keys_into(&mut buf);
+    /// assert_eq!(buf, [1, 3]);
+    /// ```
+    pub fn collect_keys_into(&self, buf: &mut Vec<K>)
+    where
+        K: Clone,
+    {
+        buf.clear();
+        buf.reserve(self.len);
+        buf.extend(self.iter().map(|(key, _)| key.clone()));
+    }
+
+    /// Removes left-most node and returns key-value pair
+    pub fn pop_first(&mut self) -> Option<(K, V)>
+    where
+        K: Ord,
+    {
+        let target = RbTree::min_node(self.root);
+
+        match target.is_nil() {
+            true => None,
+            false => self.remove_entry(target.key()),
+        }
+    }
+
+    /// Removes right-most node and returns key-value pair
+    pub fn pop_last(&mut self) -> Option<(K, V)>
+    where
+        K: Ord,
+    {
+        let target = RbTree::max_node(self.root);
+
+        match target.is_nil() {
+            true => None,
+            false => self.remove_entry(target.key()),
+        }
+    }
+
+    /// Moves the smallest `n` entries into a new tree and returns it, leaving the rest in `self`.
+    ///
+    /// If `n` is greater than the length, all entries are moved.
+    ///
+    /// It takes O(log n) using subtree sizes: the nodes are cut along the path to the `n`-th entry
+    /// and the pieces on each side are joined back, without moving any entry to another node.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// for n in [0, 1, 5, 9, 10, 20] {
+    ///     let mut tree = RbTree::new();
+    ///     for key in 0..10 {
+    ///         tree.insert(key, key * 10);
+    ///     }
+    ///
+    ///     let front = tree.split_off_n(n);
+    ///
+    ///     assert!(front.iter().map(|(key, _)| *key).eq(0..n.min(10)));
+    ///     assert!(tree.iter().map(|(key, _)| *key).eq(n.min(10)..10));
+    ///     assert!(front.is_correct_rb_tree());
+    ///     assert!(tree.is_correct_rb_tree());
+    /// }
+    ///
+    /// // chunks of a tree shaped by removals.
+    /// let mut tree = RbTree::new();
+    /// for key in 0..1000 {
+    ///     tree.insert(key, ());
+    /// }
+    /// tree.retain(|key, _| key % 7 != 3);
+    ///
+    /// let mut start = 0;
+    /// while !tree.is_empty() {
+    ///     let chunk = tree.split_off_n(97);
+    ///     assert!(chunk.is_correct_rb_tree() && tree.is_correct_rb_tree());
+    ///     assert_eq!(chunk.first_key(), Some(&start));
+    ///
+    ///     start = *chunk.last_key().unwrap() + 1;
+    ///     start += usize::from(start % 7 == 3);
+    /// }
+    /// ```
+    pub fn split_off_n(&mut self, n: usize) -> RbTree<K, V, A> {
+        let n = n.min(self.len);
+        let mut front = Self::empty();
+        let (front_root, back_root) = self.split_nodes(n);
+
+        let empty_root = std::mem::replace(&mut front.root, front_root);
+        front.pool.free(empty_root);
+        front.len = n;
+
+        self.root = back_root;
+        self.len -= n;
+
+        front
+    }
+
+    /// Splits the nodes into subtrees of the first `index` entries and the rest, and returns their roots.
+    ///
+    /// It takes O(log n) by joining the subtrees hanging off the path to the entry at `index`.
+    /// `self.root` is left dangling, so the caller must replace it.
+    fn split_nodes(&mut self, index: usize) -> (RbNode<K, V>, RbNode<K, V>) {
+        self.invalidate_lookup_cache();
+
+        let root = self.root;
+        let black_height = self.black_height();
+        let ((front, _), (back, _)) = self.split_subtree((root, black_height), index);
+
+        (front, back)
+    }
+
+    /// Splits the Black-rooted subtree with its black height into the first `index` entries and the rest.
+    ///
+    /// Both parts are returned as Black-rooted subtrees without parents, along with their black heights.
+    #[allow(clippy::type_complexity)]
+    fn split_subtree(
+        &mut self,
+        (node, black_height): (RbNode<K, V>, usize),
+        index: usize,
+    ) -> ((RbNode<K, V>, usize), (RbNode<K, V>, usize)) {
+        if node.is_nil() {
+            return ((node, 0), (self.pool.alloc(None), 0));
+        }
+
+        let child_black_height = black_height - 1;
+        let left = RbTree::detach_subtree(node.left.unwrap(), child_black_height);
+        let right = RbTree::detach_subtree(node.right.unwrap(), child_black_height);
+
+        let left_size = left.0.size;
+
+        if index <= left_size {
+            let (front, back) = self.split_subtree(left, index);
+
+            (front, self.join_subtrees(back, node, right))
+        } else {
+            let (front, back) = self.split_subtree(right, index - left_size - 1);
+
+            (self.join_subtrees(left, node, front), back)
+        }
+    }
+
+    /// Joins Black-rooted subtrees `left` and `right` with their black heights, and non-Nil node `mid` between them.
+    ///
+    /// Every key of `left` must be less than the key of `mid`, and every key of `right` greater.
+    /// Returns the root of the joined subtree, which is Black and without a parent, with its black height.
+    /// It takes O(1 + difference of black heights) besides recomputing the augmented data up to the root.
+    fn join_subtrees(
+        &mut self,
+        (mut left, left_black_height): (RbNode<K, V>, usize),
+        mut mid: RbNode<K, V>,
+        (mut right, right_black_height): (RbNode<K, V>, usize),
+    ) -> (RbNode<K, V>, usize) {
+        if left_black_height == right_black_height {
+            mid.parent = None;
+            mid.left = Some(left);
+            mid.right = Some(right);
+            left.parent = Some(mid);
+            right.parent = Some(mid);
+
+            mid.set_black();
+            mid.update_size();
+            self.augment_node(mid);
+
+            return (mid, left_black_height + 1);
+        }
+
+        // `mid` replaces the first Black node on the inner spine of the higher subtree
+        // with the black height of the lower one, so it can be Red.
+        let left_is_higher = left_black_height > right_black_height;
+        let (top, top_black_height, low_black_height) = match left_is_higher {
+            true => (left, left_black_height, right_black_height),
+            false => (right, right_black_height, left_black_height),
+        };
+
+        let mut cur = top;
+        let mut cur_black_height = top_black_height;
+
+        while cur.is_red() || cur_black_height > low_black_height {
+            if cur.is_black() {
+                cur_black_height -= 1;
+            }
+
+            cur = match left_is_higher {
+                true => cur.right.unwrap(),
+                false => cur.left.unwrap(),
+            };
+        }
+
+        let mut parent = cur.parent.unwrap();
+
+        if left_is_higher {
+            parent.right = Some(mid);
+            left = cur;
+        } else {
+            parent.left = Some(mid);
+            right = cur;
+        }
+
+        mid.parent = Some(parent);
+        mid.left = Some(left);
+        mid.right = Some(right);
+        left.parent = Some(mid);
+        right.parent = Some(mid);
+
+        mid.set_red();
+        mid.update_size();
+
+        let added = mid.size - cur.size;
+        let mut ancestor = mid.parent;
+        while let Some(mut node) = ancestor {
+            node.size += added;
+            ancestor = node.parent;
+        }
+
+        self.augment_path(Some(mid));
+        let grown = self.fix_double_red(mid);
+
+        // rotations may have moved `top` under another node.
+        let mut root = mid;
+        while let Some(parent) = root.parent {
+            root = parent;
+        }
+
+        (root, top_black_height + usize::from(grown))
+    }
+
+    /// Moves all entries with keys greater than or equal to `key` into a new tree and returns it,
+    /// leaving the smaller keys in `self`.
+    ///
+    /// It takes O(log n), finding the boundary and splitting the nodes along its path like `split_off_n`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in [1, 3, 5, 7] {
+    ///     tree.insert(key, ());
+    /// }
+    ///
+    /// // present boundary: `5` goes to the right.
+    /// let right = tree.split_off(&5);
+    /// assert!(tree.iter().map(|(key, _)| *key).eq([1, 3]));
+    /// assert!(right.iter().map(|(key, _)| *key).eq([5, 7]));
+    ///
+    /// // absent boundary: neighbors land by comparison.
+    /// let right = tree.split_off(&2);
+    /// assert!(tree.iter().map(|(key, _)| *key).eq([1]));
+    /// assert!(right.iter().map(|(key, _)| *key).eq([3]));
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 0..1000 {
+    ///     tree.insert(key, ());
+    /// }
+    /// for boundary in [900, 500, 499, 10, 0] {
+    ///     let right = tree.split_off(&boundary);
+    ///     assert_eq!(right.first_key(), Some(&boundary));
+    ///     assert_eq!(tree.len(), boundary);
+    ///     assert!(tree.is_correct_rb_tree() && right.is_correct_rb_tree());
+    /// }
+    /// ```
+    pub fn split_off<Q>(&mut self, key: &Q) -> RbTree<K, V, A>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let index = self.partition_point(|other| other.borrow() < key);
+
+        self.split_off_at(index)
+    }
+
+    /// Moves all entries with keys greater than `key` into a new tree and returns it,
+    /// leaving `key` and the smaller keys in `self`. Like `split_off`, it takes O(log n).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in [1, 3, 5, 7] {
+    ///     tree.insert(key, ());
+    /// }
+    ///
+    /// // present boundary: `5` stays on the left.
+    /// let right = tree.split_off_inclusive_left(&5);
+    /// assert!(tree.iter().map(|(key, _)| *key).eq([1, 3, 5]));
+    /// assert!(right.iter().map(|(key, _)| *key).eq([7]));
+    ///
+    /// // absent boundary: neighbors land by comparison.
+    /// let right = tree.split_off_inclusive_left(&2);
+    /// assert!(tree.iter().map(|(key, _)| *key).eq([1]));
+    /// assert!(right.iter().map(|(key, _)| *key).eq([3, 5]));
+    /// assert!(tree.is_correct_rb_tree() && right.is_correct_rb_tree());
+    /// ```
+    pub fn split_off_inclusive_left<Q>(&mut self, key: &Q) -> RbTree<K, V, A>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let index = self.partition_point(|other| other.borrow() <= key);
+
+        self.split_off_at(index)
+    }
+
+    /// Moves the entries from `index` in key order into a new tree and returns it, leaving the rest in `self`.
+    fn split_off_at(&mut self, index: usize) -> RbTree<K, V, A> {
+        let mut back = Self::empty();
+        let (front_root, back_root) = self.split_nodes(index);
+
+        let empty_root = std::mem::replace(&mut back.root, back_root);
+        back.pool.free(empty_root);
+        back.len = self.len - index;
+
+        self.root = front_root;
+        self.len = index;
+
+        back
+    }
+
+    /// Removes the entry at `index` in key order and returns its key-value pair,
+    /// or `None` if `index` is out of range.
+    ///
+    /// It takes O(log n) using subtree sizes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 0..10 {
+    ///     tree.insert(key, key * 10);
+    /// }
+    ///
+    /// assert_eq!(tree.pop_nth(0), Some((0, 0)));
+    /// assert_eq!(tree.pop_nth(4), Some((5, 50)));
+    /// assert_eq!(tree.pop_nth(7), Some((9, 90)));
+    /// assert_eq!(tree.pop_nth(7), None);
+    ///
+    /// assert!(tree.iter().map(|(key, _)| *key).eq([1, 2, 3, 4, 6, 7, 8]));
+    /// assert!(tree.is_correct_rb_tree());
+    /// ```
+    pub fn pop_nth(&mut self, index: usize) -> Option<(K, V)> {
+        let target = self.select_node(index)?;
+
+        Some(self.remove_node(target).0)
+    }
+
+    /// Consumes the tree, and returns the smallest key-value pair with the rest of the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// assert!(RbTree::<i32, ()>::new().split_first().is_none());
+    ///
+    /// let mut tree = RbTree::new();
+    /// tree.insert(1, "a");
+    ///
+    /// let (first, rest) = tree.split_first().unwrap();
+    /// assert_eq!(first, (1, "a"));
+    /// assert!(rest.is_empty());
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 0..10 {
+    ///     tree.insert(key, key * 10);
+    /// }
+    ///
+    /// let (first, rest) = tree.split_first().unwrap();
+    /// assert_eq!(first, (0, 0));
+    /// assert!(rest.iter().map(|(key, _)| *key).eq(1..10));
+    /// assert!(rest.is_correct_rb_tree());
+    /// ```
+    pub fn split_first(mut self) -> Option<((K, V), Self)> {
+        let target = self.first_node()?;
+        let (first, _) = self.remove_node(target);
+
+        Some((first, self))
+    }
+
+    /// Consumes the tree, and returns the largest key-value pair with the rest of the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// assert!(RbTree::<i32, ()>::new().split_last().is_none());
+    ///
+    /// let mut tree = RbTree::new();
+    /// tree.insert(1, "a");
+    ///
+    /// let (last, rest) = tree.split_last().unwrap();
+    /// assert_eq!(last, (1, "a"));
+    /// assert!(rest.is_empty());
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 0..10 {
+    ///     tree.insert(key, key * 10);
+    /// }
+    ///
+    /// let (last, rest) = tree.split_last().unwrap();
+    /// assert_eq!(last, (9, 90));
+    /// assert!(rest.iter().map(|(key, _)| *key).eq(0..9));
+    /// assert!(rest.is_correct_rb_tree());
+    /// ```
+    pub fn split_last(mut self) -> Option<((K, V), Self)> {
+        let target = self.last_node()?;
+        let (last, _) = self.remove_node(target);
+
+        Some((last, self))
     }
 
-    /// Returns a reference to the value corresponding to the key.
-    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    /// Removes the smallest entries while `pred` returns `true` for them, and returns the removed key-value pairs
+    /// in ascending key order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut deadlines = RbTree::new();
+    /// for time in [30, 10, 50, 20, 40] {
+    ///     deadlines.insert(time, format!("task {time}"));
+    /// }
+    ///
+    /// let now = 30;
+    /// let expired = deadlines.remove_min_while(|time, _| *time <= now);
+    ///
+    /// assert!(expired.iter().map(|(time, _)| *time).eq([10, 20, 30]));
+    /// assert!(deadlines.iter().map(|(time, _)| *time).eq([40, 50]));
+    /// assert!(deadlines.is_correct_rb_tree());
+    /// ```
+    pub fn remove_min_while<F>(&mut self, mut pred: F) -> Vec<(K, V)>
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        let mut removed = Vec::new();
+
+        while let Some(target) = self.first_node() {
+            if !pred(target.key(), target.value()) {
+                break;
+            }
+
+            removed.push(self.remove_node(target).0);
+        }
+
+        removed
+    }
+
+    /// Removes the largest entries while `pred` returns `true` for them, and returns the removed key-value pairs
+    /// in descending key order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 0..10 {
+    ///     tree.insert(key, ());
+    /// }
+    ///
+    /// let removed = tree.remove_max_while(|key, _| *key >= 7);
+    ///
+    /// assert!(removed.iter().map(|(key, _)| *key).eq([9, 8, 7]));
+    /// assert!(tree.iter().map(|(key, _)| *key).eq(0..7));
+    /// ```
+    pub fn remove_max_while<F>(&mut self, mut pred: F) -> Vec<(K, V)>
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        let mut removed = Vec::new();
+
+        loop {
+            let target = RbTree::max_node(self.root);
+
+            if target.is_nil() || !pred(target.key(), target.value()) {
+                break;
+            }
+
+            removed.push(self.remove_node(target).0);
+        }
+
+        removed
+    }
+
+    /// Removes the smallest entries if `from_front` is `true`, or the largest entries otherwise,
+    /// until the tree has at most `n` entries. Returns the removed key-value pairs in removed order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in 0..100 {
+    ///     tree.insert(key, ());
+    /// }
+    ///
+    /// let evicted = tree.truncate_to(10, true);
+    ///
+    /// assert_eq!(evicted.len(), 90);
+    /// assert!(evicted.iter().map(|(key, _)| *key).eq(0..90));
+    /// assert!(tree.iter().map(|(key, _)| *key).eq(90..100));
+    /// assert_eq!(tree.len(), 10);
+    /// assert!(tree.is_correct_rb_tree());
+    ///
+    /// let evicted = tree.truncate_to(8, false);
+    /// assert!(evicted.iter().map(|(key, _)| *key).eq([99, 98]));
+    /// ```
+    pub fn truncate_to(&mut self, n: usize, from_front: bool) -> Vec<(K, V)> {
+        let mut evicted = Vec::with_capacity(self.len.saturating_sub(n));
+
+        while self.len > n {
+            let target = match from_front {
+                true => self.first_node(),
+                false => self.last_node(),
+            };
+
+            evicted.extend(target.map(|target| self.remove_node(target).0));
+        }
+
+        evicted
+    }
+}
+
+// Mutable references to values are only handed out by trees without augmentation,
+// since the tree doesn't see writes through them.
+impl<K, V> RbTree<K, V> {
+    /// Returns a mutable reference to the value corresponding to the key.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let target = self.lookup(key)?;
+
+        unsafe { Some(target.value_mut()) }
+    }
+
+    /// Returns mutable references to the values of two distinct keys.
+    ///
+    /// Returns `None` if the keys are equal or either key is not in the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut accounts = RbTree::new();
+    /// accounts.insert("alice", 100);
+    /// accounts.insert("bob", 50);
+    ///
+    /// if let Some((from, to)) = accounts.get2_mut("alice", "bob") {
+    ///     *from -= 30;
+    ///     *to += 30;
+    /// }
+    ///
+    /// assert_eq!(accounts["alice"], 70);
+    /// assert_eq!(accounts["bob"], 80);
+    ///
+    /// assert!(accounts.get2_mut("alice", "alice").is_none());
+    /// assert!(accounts.get2_mut("alice", "carol").is_none());
+    /// ```
+    pub fn get2_mut<Q>(&mut self, a: &Q, b: &Q) -> Option<(&mut V, &mut V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let a = self.lookup(a)?;
+        let b = self.lookup(b)?;
+
+        if a == b {
+            return None;
+        }
+
+        // distinct nodes, so the references don't alias.
+        unsafe { Some((a.value_mut(), b.value_mut())) }
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    ///
+    /// # Panics
+    ///
+    /// Panics with `msg` if the key is not in the tree.
+    /// The panic location is reported at the caller.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut stock = RbTree::new();
+    /// stock.insert("apple", 3);
+    ///
+    /// *stock.value_mut("apple", "apple should be in stock") += 1;
+    /// assert_eq!(stock["apple"], 4);
+    /// ```
+    ///
+    /// A missing key panics with the message:
+    ///
+    /// ```should_panic
+    /// use rbtree::RbTree;
+    ///
+    /// let mut stock = RbTree::new();
+    /// stock.insert("apple", 3);
+    ///
+    /// // panics with "pear should be in stock"
+    /// *stock.value_mut("pear", "pear should be in stock") += 1;
+    /// ```
+    #[track_caller]
+    pub fn value_mut<Q>(&mut self, key: &Q, msg: &str) -> &mut V
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match self.lookup(key) {
+            Some(target) => unsafe { target.value_mut() },
+            None => panic!("{msg}"),
+        }
+    }
+
+    /// Returns a mutable reference to the value of the key, inserting `value` if the key is not in the tree.
+    ///
+    /// The boolean is `true` if a new entry was inserted. Otherwise `value` is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::rc::Rc;
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// let value = Rc::new(0);
+    ///
+    /// let (stored, inserted) = tree.get_or_insert_entry(1, Rc::clone(&value));
+    /// assert!(inserted);
+    /// assert!(Rc::ptr_eq(stored, &value));
+    ///
+    /// // hit: the new value is dropped.
+    /// let (stored, inserted) = tree.get_or_insert_entry(1, Rc::new(1));
+    /// assert!(!inserted);
+    /// assert_eq!(**stored, 0);
+    /// assert_eq!(Rc::strong_count(&value), 2);
+    /// ```
+    pub fn get_or_insert_entry(&mut self, key: K, value: V) -> (&mut V, bool)
+    where
+        K: Ord,
+    {
+        self.entry(key).or_insert_tracked(value)
+    }
+
+    /// Returns a mutable reference to the value of the key, inserting the result of `f` called with the key
+    /// if the key is not in the tree.
+    ///
+    /// It searches the tree once, like `entry`, and `f` is only called if the key is not in the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut connections = RbTree::new();
+    /// let mut opened = Vec::new();
+    ///
+    /// let mut open = |host: &&str| {
+    ///     opened.push(host.to_string());
+    ///     format!("connection to {host}")
+    /// };
+    ///
+    /// let connection = connections.get_or_insert_with_key("example.com", &mut open);
+    /// assert_eq!(connection, "connection to example.com");
+    ///
+    /// // hit: `f` is not called.
+    /// connections.get_or_insert_with_key("example.com", &mut open);
+    /// connections.get_or_insert_with_key("example.org", &mut open);
+    ///
+    /// assert_eq!(opened, ["example.com", "example.org"]);
+    /// assert_eq!(connections.len(), 2);
+    /// ```
+    pub fn get_or_insert_with_key<F>(&mut self, key: K, f: F) -> &mut V
+    where
+        K: Ord,
+        F: FnOnce(&K) -> V,
+    {
+        self.entry(key).or_insert_with_key(f)
+    }
+
+    /// Returns an iterator over key-value pairs with mutable values in ascending key order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut tree = RbTree::new();
+    /// for key in (0..100_000).rev() {
+    ///     tree.insert(key, key);
+    /// }
+    ///
+    /// for (_, value) in tree.iter_mut() {
+    ///     *value *= 2;
+    /// }
+    ///
+    /// assert_eq!(tree.iter_mut().len(), 100_000);
+    /// assert!(tree.iter().map(|(key, value)| (*key, *value)).eq((0..100_000).map(|key| (key, key * 2))));
+    /// assert!(tree.into_iter().map(|(key, _)| key).eq(0..100_000));
+    /// ```
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            cur: self.root,
+            stack: self.new_iter_stack(),
+            len: self.len,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns an iterator over key-value pairs within `range` with mutable values, in ascending key order.
+    pub fn range_mut<Q, R>(&mut self, range: R) -> RangeMut<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let (cur, stack, last) = self.range_state(&range);
+
+        RangeMut {
+            cur,
+            stack,
+            last,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns an iterator over mutable values whose keys are within `range`, in ascending key order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut prices = RbTree::new();
+    /// for (item, price) in [(1, 100), (2, 200), (3, 300), (4, 400)] {
+    ///     prices.insert(item, price);
+    /// }
+    ///
+    /// // discount items in the middle bracket.
+    /// for price in prices.values_in_range_mut(2..4) {
+    ///     *price -= 50;
+    /// }
+    ///
+    /// assert_eq!(prices[&1], 100);
+    /// assert_eq!(prices[&2], 150);
+    /// assert_eq!(prices[&3], 250);
+    /// assert_eq!(prices[&4], 400);
+    /// ```
+    pub fn values_in_range_mut<Q, R>(&mut self, range: R) -> impl Iterator<Item = &mut V>
     where
         K: Borrow<Q>,
         Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
     {
-        let mut target = self.find_nearest_node(key);
+        self.range_mut(range).map(|(_, value)| value)
+    }
+
+    /// Returns a mutable reference to the value of the first entry, or `None` if the tree is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut counters = RbTree::new();
+    /// assert_eq!(counters.first_value_mut(), None);
+    ///
+    /// counters.insert(1, 0);
+    /// counters.insert(2, 0);
+    /// counters.insert(3, 0);
+    ///
+    /// *counters.first_value_mut().unwrap() += 1;
+    /// *counters.last_value_mut().unwrap() += 10;
+    ///
+    /// assert!(counters.iter().eq([(&1, &1), (&2, &0), (&3, &10)]));
+    /// ```
+    pub fn first_value_mut(&mut self) -> Option<&mut V> {
+        let target = RbTree::min_node(self.root);
+
+        match target.is_nil() {
+            true => None,
+            false => Some(unsafe { target.value_mut() }),
+        }
+    }
+
+    /// Returns the key and a mutable reference to the value of the first entry, or `None` if the tree is empty.
+    ///
+    /// The key is shared, so the key order can't be changed through it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::RbTree;
+    ///
+    /// let mut deadlines = RbTree::new();
+    /// assert_eq!(deadlines.first_key_value_mut(), None);
+    ///
+    /// deadlines.insert(10, "write report");
+    /// deadlines.insert(20, "review");
+    /// deadlines.insert(30, "ship");
+    ///
+    /// if let Some((deadline, task)) = deadlines.first_key_value_mut() {
+    ///     assert_eq!(*deadline, 10);
+    ///     *task = "write report (late)";
+    /// }
+    /// if let Some((deadline, task)) = deadlines.last_key_value_mut() {
+    ///     assert_eq!(*deadline, 30);
+    ///     *task = "ship it";
+    /// }
+    ///
+    /// assert!(deadlines.iter().eq([
+    ///     (&10, &"write report (late)"),
+    ///     (&20, &"review"),
+    ///     (&30, &"ship it"),
+    /// ]));
+    /// ```
+    pub fn first_key_value_mut(&mut self) -> Option<(&K, &mut V)> {
+        let target = RbTree::min_node(self.root);
+
+        match target.is_nil() {
+            true => None,
+            false => unsafe { Some(target.key_value_mut()) },
+        }
+    }
+
+    /// Returns a mutable reference to the value of the last entry, or `None` if the tree is empty.
+    pub fn last_value_mut(&mut self) -> Option<&mut V> {
+        let target = RbTree::max_node(self.root);
+
+        match target.is_nil() {
+            true => None,
+            false => Some(unsafe { target.value_mut() }),
+        }
+    }
+
+    /// Returns the key and a mutable reference to the value of the last entry, or `None` if the tree is empty.
+    ///
+    /// The key is shared, so the key order can't be changed through it.
+    pub fn last_key_value_mut(&mut self) -> Option<(&K, &mut V)> {
+        let target = RbTree::max_node(self.root);
+
         match target.is_nil() {
             true => None,
-            false => Some(unsafe { (*target.as_ptr()).value.assume_init_ref() }),
+            false => unsafe { Some(target.key_value_mut()) },
+        }
+    }
+}
+
+// Walking over nodes doesn't depend on the augmentation, so trees of any augmentation share these.
+impl<K, V> RbTree<K, V> {
+    /// Initialize Nil node `node` as a balanced subtree of `items`, taking the middle item as `node`.
+    fn build_subtree(
+        mut node: RbNode<K, V>,
+        items: &mut [Option<(K, V)>],
+        depth: usize,
+        red_depth: usize,
+        pool: &mut NodePool<K, V>,
+    ) {
+        if items.is_empty() {
+            return;
+        }
+
+        let mid = items.len() / 2;
+        let (key, value) = items[mid].take().unwrap();
+
+        // root should be Black even if it's the deepest level
+        let rb_node_type = match depth > 0 && depth == red_depth {
+            true => RbNodeType::Red,
+            false => RbNodeType::Black,
+        };
+
+        node.init(key, value, rb_node_type, pool);
+
+        let (left_items, right_items) = items.split_at_mut(mid);
+        RbTree::build_subtree(node.left.unwrap(), left_items, depth + 1, red_depth, pool);
+        RbTree::build_subtree(
+            node.right.unwrap(),
+            &mut right_items[1..],
+            depth + 1,
+            red_depth,
+            pool,
+        );
+
+        node.update_size();
+    }
+
+    /// find left-most non-Nil node starting from input node.
+    ///
+    /// It returns Nil only if input node is Nil.
+    fn min_node(node: RbNode<K, V>) -> RbNode<K, V> {
+        if node.is_nil() {
+            return node;
+        }
+
+        let mut cur = node;
+
+        loop {
+            let left = cur.left.unwrap();
+            if left.is_nil() {
+                break;
+            }
+
+            cur = left;
+        }
+
+        cur
+    }
+
+    /// find right-most non-Nil node starting from input node.
+    ///
+    /// It returns Nil only if input node is Nil.
+    fn max_node(node: RbNode<K, V>) -> RbNode<K, V> {
+        if node.is_nil() {
+            return node;
+        }
+
+        let mut cur = node;
+
+        loop {
+            let right = cur.right.unwrap();
+            if right.is_nil() {
+                break;
+            }
+
+            cur = right;
+        }
+
+        cur
+    }
+
+    /// find the next non-Nil node of input node in key order.
+    ///
+    /// It returns `None` if input node is right-most.
+    fn next_node(node: RbNode<K, V>) -> Option<RbNode<K, V>> {
+        let right = node.right.unwrap();
+        if !right.is_nil() {
+            return Some(RbTree::min_node(right));
+        }
+
+        let mut cur = node;
+
+        while let Some(parent) = cur.parent {
+            if parent.left == Some(cur) {
+                return Some(parent);
+            }
+
+            cur = parent;
+        }
+
+        None
+    }
+
+    /// find the previous non-Nil node of input node in key order.
+    ///
+    /// It returns `None` if input node is left-most.
+    fn prev_node(node: RbNode<K, V>) -> Option<RbNode<K, V>> {
+        let left = node.left.unwrap();
+        if !left.is_nil() {
+            return Some(RbTree::max_node(left));
         }
-    }
 
-    /// Returns a reference to the key-value pair corresponding to the key.
-    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
-    where
-        K: Borrow<Q>,
-        Q: Ord + ?Sized,
-    {
-        let mut target = self.find_nearest_node(key);
+        let mut cur = node;
 
-        match target.is_nil() {
-            true => None,
-            false => Some(unsafe {
-                (
-                    (*target.as_ptr()).key.assume_init_ref(),
-                    (*target.as_ptr()).value.assume_init_ref(),
-                )
-            }),
+        while let Some(parent) = cur.parent {
+            if parent.right == Some(cur) {
+                return Some(parent);
+            }
+
+            cur = parent;
         }
+
+        None
     }
 
-    /// Returns a mutable reference to the value corresponding to the key.
-    pub fn get_mut<Q>(&self, key: &Q) -> Option<&V>
-    where
-        K: Borrow<Q>,
-        Q: Ord + ?Sized,
-    {
-        let mut target = self.find_nearest_node(key);
+    /// find the index of non-Nil node in key order using subtree sizes.
+    fn node_index(node: RbNode<K, V>) -> usize {
+        let mut index = node.left.unwrap().size;
+        let mut cur = node;
 
-        match target.is_nil() {
-            true => None,
-            false => Some(unsafe { (*target.as_ptr()).value.assume_init_mut() }),
+        while let Some(parent) = cur.parent {
+            if parent.right == Some(cur) {
+                index += parent.left.unwrap().size + 1;
+            }
+
+            cur = parent;
         }
-    }
 
-    /// Check if tree has a node with input key.
-    pub fn contains_key<Q>(&self, key: &Q) -> bool
-    where
-        K: Borrow<Q>,
-        Q: Ord + ?Sized,
-    {
-        !self.find_nearest_node(key).is_nil()
+        index
     }
 
-    /// Makes the tree empty.
+    /// Returns the black count of the subtree of `root`, or `Err` if its paths have different black counts.
     ///
-    /// root node turns into Nil node.
-    pub fn clear(&mut self) {
-        if self.root.is_nil() {
-            return;
-        }
-
-        let mut stack = vec![self.root];
+    /// It visits nodes in post-order with an explicit stack, so a tall tree can't overflow the call stack.
+    fn check_rb_tree_attribute(root: RbNode<K, V>) -> Result<u64, ()> {
+        // black counts of visited subtrees, which are consumed by their parents
+        let mut black_counts = Vec::new();
+        let mut stack = vec![(root, false)];
+
+        while let Some((node, children_visited)) = stack.pop() {
+            if !children_visited {
+                stack.push((node, true));
+                stack.extend(node.right.map(|right| (right, false)));
+                stack.extend(node.left.map(|left| (left, false)));
+                continue;
+            }
 
-        while !stack.is_empty() {
-            let mut cur = stack.pop().unwrap();
+            let right_black_count = match node.right {
+                Some(_) => black_counts.pop().unwrap(),
+                None => 0,
+            };
 
-            let (left_is_nil, right_is_nil) =
-                (cur.left.unwrap().is_nil(), cur.right.unwrap().is_nil());
+            let left_black_count = match node.left {
+                Some(_) => black_counts.pop().unwrap(),
+                None => 0,
+            };
 
-            if left_is_nil && right_is_nil {
-                cur.uninit();
-            } else {
-                stack.push(cur);
+            if left_black_count != right_black_count {
+                return Err(());
+            }
 
-                if !right_is_nil {
-                    stack.push(cur.right.unwrap());
-                }
+            let self_black_count = match node.is_black() {
+                true => 1,
+                false => 0,
+            };
 
-                if !left_is_nil {
-                    stack.push(cur.left.unwrap());
-                }
-            }
+            black_counts.push(left_black_count + self_black_count);
         }
+
+        Ok(black_counts.pop().unwrap())
     }
 
-    /// Removes left-most node and returns key-value pair
-    pub fn pop_first(&mut self) -> Option<(K, V)>
-    where
-        K: Ord,
-    {
-        let target = RbTree::min_node(self.root);
+    /// Create `Range` from the traversal state given by `range_state`.
+    fn new_range<'a>(
+        cur: RbNode<K, V>,
+        stack: Vec<RbNode<K, V>>,
+        last: Option<RbNode<K, V>>,
+    ) -> Range<'a, K, V> {
+        let remaining = match (stack.last(), last) {
+            (Some(&first), Some(last)) => RbTree::node_index(last) - RbTree::node_index(first) + 1,
+            _ => 0,
+        };
 
-        match target.is_nil() {
-            true => None,
-            false => self.remove_entry(target.key()),
+        Range {
+            cur,
+            stack,
+            back: last,
+            remaining,
+            _marker: PhantomData,
         }
     }
 
-    /// Removes right-most node and returns key-value pair
-    pub fn pop_last(&mut self) -> Option<(K, V)>
-    where
-        K: Ord,
-    {
-        let target = RbTree::max_node(self.root);
+    /// Detaches child `node` with black height `black_height` from its parent, turning it Black if it's Red.
+    ///
+    /// Returns `node` with its new black height.
+    fn detach_subtree(mut node: RbNode<K, V>, black_height: usize) -> (RbNode<K, V>, usize) {
+        node.parent = None;
+
+        match node.is_red() {
+            true => {
+                node.set_black();
+                (node, black_height + 1)
+            }
+            false => (node, black_height),
+        }
+    }
+}
 
-        match target.is_nil() {
-            true => None,
-            false => self.remove_entry(target.key()),
+impl<K, V, A: Augment<K, V>> Default for RbTree<K, V, A> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+/// Merges trees into `self` in order, so values from later trees win for duplicated keys.
+///
+/// # Examples
+///
+/// ```
+/// use rbtree::RbTree;
+///
+/// let mut base = RbTree::new();
+/// base.insert(1, "base");
+/// base.insert(2, "base");
+///
+/// let trees = vec![
+///     RbTree::from_sorted_iter([(2, "a"), (3, "a")]),
+///     RbTree::from_sorted_iter([(3, "b"), (4, "b")]),
+///     RbTree::from_sorted_iter([(1, "c"), (4, "c"), (5, "c")]),
+/// ];
+///
+/// base.extend(trees);
+///
+/// assert!(base.iter().eq([(&1, &"c"), (&2, &"a"), (&3, &"b"), (&4, &"c"), (&5, &"c")]));
+/// assert!(base.is_correct_rb_tree());
+/// ```
+impl<K, V, A> Extend<RbTree<K, V, A>> for RbTree<K, V, A>
+where
+    K: Ord,
+    A: Augment<K, V>,
+{
+    fn extend<I: IntoIterator<Item = RbTree<K, V, A>>>(&mut self, iter: I) {
+        for tree in iter {
+            self.merge_sorted(tree);
         }
     }
 }
 
-impl<K, V> Debug for RbTree<K, V>
+impl<K, V, A> Debug for RbTree<K, V, A>
 where
     K: Ord + Debug,
     V: Debug,
+    A: Augment<K, V>,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("RbTree")
@@ -641,20 +5292,16 @@ where
     }
 }
 
-impl<K, Q, V> Index<&Q> for RbTree<K, V>
+impl<K, Q, V, A> Index<&Q> for RbTree<K, V, A>
 where
     K: Borrow<Q>,
     Q: Ord + ?Sized,
+    A: Augment<K, V>,
 {
     type Output = V;
 
     fn index(&self, index: &Q) -> &Self::Output {
-        let mut target = self.find_nearest_node(index);
-
-        match target.is_nil() {
-            true => panic!("key not found"),
-            false => unsafe { (*target.as_ptr()).value.assume_init_ref() },
-        }
+        self.get(index).expect("key not found")
     }
 }
 
@@ -664,16 +5311,11 @@ where
     Q: Ord + ?Sized,
 {
     fn index_mut(&mut self, index: &Q) -> &mut Self::Output {
-        let mut target = self.find_nearest_node(index);
-
-        match target.is_nil() {
-            true => panic!("key not found"),
-            false => unsafe { (*target.as_ptr()).value.assume_init_mut() },
-        }
+        self.get_mut(index).expect("key not found")
     }
 }
 
-impl<K, V> Drop for RbTree<K, V> {
+impl<K, V, A: Augment<K, V>> Drop for RbTree<K, V, A> {
     fn drop(&mut self) {
         self.clear();
         unsafe {
@@ -682,6 +5324,40 @@ impl<K, V> Drop for RbTree<K, V> {
     }
 }
 
+/// Removes `node`, whose value was moved out, from `tree` when dropped.
+///
+/// It's forgotten once a new value is written, so it only drops while unwinding.
+struct RemoveOnUnwind<'a, K, V, A: Augment<K, V>> {
+    tree: &'a mut RbTree<K, V, A>,
+    node: RbNode<K, V>,
+}
+
+impl<K, V, A: Augment<K, V>> Drop for RemoveOnUnwind<'_, K, V, A> {
+    fn drop(&mut self) {
+        let ((key, value), _) = self.tree.remove_node(self.node);
+        drop(key);
+        std::mem::forget(value);
+    }
+}
+
+/// Recomputes the augmented data from `node` up to the root of `tree` when dropped,
+/// or the augmented data of the whole tree if `node` is `None`.
+///
+/// It's held while a closure changes values, so a panicking closure doesn't leave the augmented data stale.
+struct AugmentOnDrop<'a, K, V, A: Augment<K, V>> {
+    tree: &'a RbTree<K, V, A>,
+    node: Option<RbNode<K, V>>,
+}
+
+impl<K, V, A: Augment<K, V>> Drop for AugmentOnDrop<'_, K, V, A> {
+    fn drop(&mut self) {
+        match self.node {
+            Some(node) => self.tree.augment_path(Some(node)),
+            None => self.tree.augment_all(),
+        }
+    }
+}
+
 fn iter_next<K, V>(
     cur: RbNode<K, V>,
     stack: &mut Vec<RbNode<K, V>>,
@@ -702,9 +5378,66 @@ fn iter_next<K, V>(
     None
 }
 
+/// Check if `key` is not before the start bound of `range`.
+fn is_after_start<Q, R>(key: &Q, range: &R) -> bool
+where
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{
+    match range.start_bound() {
+        Bound::Included(start) => key >= start,
+        Bound::Excluded(start) => key > start,
+        Bound::Unbounded => true,
+    }
+}
+
+/// Check if `key` is not after the end bound of `range`.
+fn is_before_end<Q, R>(key: &Q, range: &R) -> bool
+where
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{
+    match range.end_bound() {
+        Bound::Included(end) => key <= end,
+        Bound::Excluded(end) => key < end,
+        Bound::Unbounded => true,
+    }
+}
+
+/// The result of `RbTree::validate_and_repair`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepairReport {
+    /// Whether paths from a node to its Nil nodes have different numbers of black nodes.
+    pub black_height_mismatch: bool,
+    /// The number of red nodes which are the root or have a red parent.
+    pub red_violations: usize,
+    /// The number of nodes whose stored subtree size is wrong.
+    pub size_mismatches: usize,
+    /// Whether the stored length differs from the number of nodes.
+    pub len_mismatch: bool,
+    /// The number of adjacent entries which are not in strictly ascending key order.
+    pub misordered_pairs: usize,
+    /// The number of entries dropped because of duplicated keys when rebuilding.
+    pub removed_duplicates: usize,
+    /// Whether the tree was rebuilt.
+    pub rebuilt: bool,
+}
+
+impl RepairReport {
+    /// Returns `true` if no invariant was broken.
+    pub fn is_clean(&self) -> bool {
+        !self.black_height_mismatch
+            && self.red_violations == 0
+            && self.size_mismatches == 0
+            && !self.len_mismatch
+            && self.misordered_pairs == 0
+    }
+}
+
 pub struct Iter<'a, K, V> {
     cur: RbNode<K, V>,
     stack: Vec<RbNode<K, V>>,
+    len: usize,
     _marker: PhantomData<(&'a K, &'a V)>,
 }
 
@@ -713,37 +5446,36 @@ impl<'a, K, V> Iterator for Iter<'a, K, V> {
 
     fn next(&mut self) -> Option<Self::Item> {
         match iter_next(self.cur, &mut self.stack) {
-            Some((cur, mut next)) => {
+            Some((cur, next)) => {
                 self.cur = cur;
-                unsafe {
-                    Some((
-                        (*next.as_ptr()).key.assume_init_ref(),
-                        (*next.as_ptr()).value.assume_init_ref(),
-                    ))
-                }
+                self.len -= 1;
+                unsafe { Some(next.key_value_ref()) }
             }
             None => None,
         }
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
 }
 
-impl<'a, K, V> IntoIterator for &'a RbTree<K, V> {
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<'a, K, V, A: Augment<K, V>> IntoIterator for &'a RbTree<K, V, A> {
     type Item = (&'a K, &'a V);
 
     type IntoIter = Iter<'a, K, V>;
 
     fn into_iter(self) -> Self::IntoIter {
-        Iter {
-            cur: self.root,
-            stack: Vec::new(),
-            _marker: PhantomData,
-        }
+        self.iter()
     }
 }
 
 pub struct IterMut<'a, K, V> {
     cur: RbNode<K, V>,
     stack: Vec<RbNode<K, V>>,
+    len: usize,
     _marker: PhantomData<(&'a K, &'a mut V)>,
 }
 
@@ -752,47 +5484,52 @@ impl<'a, K, V> Iterator for IterMut<'a, K, V> {
 
     fn next(&mut self) -> Option<Self::Item> {
         match iter_next(self.cur, &mut self.stack) {
-            Some((cur, mut next)) => {
+            Some((cur, next)) => {
                 self.cur = cur;
-                unsafe {
-                    Some((
-                        (*next.as_ptr()).key.assume_init_ref(),
-                        (*next.as_ptr()).value.assume_init_mut(),
-                    ))
-                }
+                self.len -= 1;
+                unsafe { Some(next.key_value_mut()) }
             }
             None => None,
         }
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
 }
 
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+
 impl<'a, K, V> IntoIterator for &'a mut RbTree<K, V> {
     type Item = (&'a K, &'a mut V);
 
     type IntoIter = IterMut<'a, K, V>;
 
     fn into_iter(self) -> Self::IntoIter {
-        IterMut {
-            cur: self.root,
-            stack: Vec::new(),
-            _marker: PhantomData,
-        }
+        self.iter_mut()
     }
 }
 
-pub struct IntoIter<K, V> {
-    _rb_tree: RbTree<K, V>,
+pub struct IntoIter<K, V, A: Augment<K, V> = NoAugment> {
+    _rb_tree: RbTree<K, V, A>,
     cur: RbNode<K, V>,
     stack: Vec<RbNode<K, V>>,
+    back: Option<RbNode<K, V>>,
+    len: usize,
 }
 
-impl<K, V> Iterator for IntoIter<K, V> {
+impl<K, V, A: Augment<K, V>> Iterator for IntoIter<K, V, A> {
     type Item = (K, V);
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+
         match iter_next(self.cur, &mut self.stack) {
             Some((cur, mut next)) => {
                 self.cur = cur;
+                self.len -= 1;
                 next.key_value_moved = true;
                 unsafe {
                     Some((
@@ -804,20 +5541,200 @@ impl<K, V> Iterator for IntoIter<K, V> {
             None => None,
         }
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K, V, A: Augment<K, V>> DoubleEndedIterator for IntoIter<K, V, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let mut back = self.back?;
+        self.back = RbTree::prev_node(back);
+        self.len -= 1;
+        back.key_value_moved = true;
+
+        unsafe { Some((back.key.assume_init_read(), back.value.assume_init_read())) }
+    }
 }
 
-impl<K, V> IntoIterator for RbTree<K, V> {
+impl<K, V, A: Augment<K, V>> ExactSizeIterator for IntoIter<K, V, A> {}
+
+impl<K, V, A: Augment<K, V>> IntoIterator for RbTree<K, V, A> {
     type Item = (K, V);
 
-    type IntoIter = IntoIter<K, V>;
+    type IntoIter = IntoIter<K, V, A>;
 
     fn into_iter(self) -> Self::IntoIter {
         let cur = self.root;
+        let stack = self.new_iter_stack();
+        let back = (!self.is_empty()).then(|| RbTree::max_node(self.root));
+        let len = self.len;
 
         IntoIter {
             _rb_tree: self, // To prevent rb_tree from drop
             cur,
-            stack: Vec::new(),
+            stack,
+            back,
+            len,
+        }
+    }
+}
+
+pub struct Range<'a, K, V> {
+    cur: RbNode<K, V>,
+    stack: Vec<RbNode<K, V>>,
+    back: Option<RbNode<K, V>>,
+    remaining: usize,
+    _marker: PhantomData<(&'a K, &'a V)>,
+}
+
+impl<'a, K, V> Iterator for Range<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let (cur, next) = iter_next(self.cur, &mut self.stack)?;
+        self.cur = cur;
+        self.remaining -= 1;
+
+        unsafe { Some(next.key_value_ref()) }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Range<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let back = self.back?;
+        self.back = RbTree::prev_node(back);
+        self.remaining -= 1;
+
+        unsafe { Some(back.key_value_ref()) }
+    }
+}
+
+impl<K, V> ExactSizeIterator for Range<'_, K, V> {}
+
+pub struct RangeMut<'a, K, V> {
+    cur: RbNode<K, V>,
+    stack: Vec<RbNode<K, V>>,
+    last: Option<RbNode<K, V>>,
+    _marker: PhantomData<(&'a K, &'a mut V)>,
+}
+
+impl<'a, K, V> Iterator for RangeMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let last = self.last?;
+
+        match iter_next(self.cur, &mut self.stack) {
+            Some((cur, next)) => {
+                self.cur = cur;
+
+                if next == last {
+                    self.last = None;
+                }
+
+                unsafe { Some(next.key_value_mut()) }
+            }
+            None => None,
+        }
+    }
+}
+
+pub struct DrainRange<'a, K, V, A: Augment<K, V> = NoAugment> {
+    tree: &'a mut RbTree<K, V, A>,
+    next: Option<RbNode<K, V>>,
+    remaining: usize,
+}
+
+impl<K, V, A: Augment<K, V>> Iterator for DrainRange<'_, K, V, A> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let (removed_key_value, next) = self.tree.remove_node(self.next?);
+        self.next = next;
+        self.remaining -= 1;
+
+        Some(removed_key_value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V, A: Augment<K, V>> ExactSizeIterator for DrainRange<'_, K, V, A> {}
+
+impl<K, V, A: Augment<K, V>> Drop for DrainRange<'_, K, V, A> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::{RbNodeType, RbTree};
+
+    /// Builds a tree of `len` Black nodes, each one the right child of the previous one.
+    ///
+    /// It's as high as it's long, and its black height differs on every path.
+    fn right_chain(len: usize) -> RbTree<usize, ()> {
+        let mut tree = RbTree::new();
+        let mut cur = tree.root;
+
+        for key in 0..len {
+            cur.init(key, (), RbNodeType::Black, &mut tree.pool);
+            cur.size = len - key;
+            cur = cur.right.unwrap();
         }
+
+        tree.len = len;
+
+        tree
+    }
+
+    #[test]
+    fn validate_deep_tree_on_small_stack() {
+        // a recursive check would need a frame per level, far more than 64 KiB.
+        thread::Builder::new()
+            .stack_size(64 * 1024)
+            .spawn(|| {
+                let mut tree = right_chain(100_000);
+                assert_eq!(tree.height(), 100_000);
+                assert!(!tree.is_correct_rb_tree());
+
+                let report = tree.validate_and_repair();
+                assert!(report.black_height_mismatch);
+                assert_eq!(report.size_mismatches, 0);
+                assert!(report.rebuilt);
+
+                assert!(tree.is_correct_rb_tree());
+                assert!(tree.iter().map(|(key, _)| *key).eq(0..100_000));
+            })
+            .unwrap()
+            .join()
+            .unwrap();
     }
 }
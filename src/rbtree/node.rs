@@ -5,6 +5,8 @@ use std::{
     ptr::NonNull,
 };
 
+use super::pool::NodePool;
+
 #[derive(Clone, Copy, Debug)]
 pub enum RbNodeType {
     Red,
@@ -20,6 +22,8 @@ pub enum RbNodeType {
 ///
 /// `key` and `value` are init unless the node is Nil.
 ///
+/// `size` is the number of non-Nil nodes in the subtree rooted at the node, so it's 0 for Nil.
+///
 /// ## Safety
 ///
 /// `key_value_moved` must be correct not to occur double-free or memory leak.
@@ -28,6 +32,7 @@ pub struct RawNode<K, V> {
     pub value: MaybeUninit<V>,
     pub key_value_moved: bool,
     pub rb_node_type: RbNodeType,
+    pub size: usize,
     pub parent: Option<RbNode<K, V>>,
     pub left: Option<RbNode<K, V>>,
     pub right: Option<RbNode<K, V>>,
@@ -62,7 +67,7 @@ impl<K, V> Eq for RbNode<K, V> {}
 
 impl<K, V> Clone for RbNode<K, V> {
     fn clone(&self) -> Self {
-        Self(self.0)
+        *self
     }
 }
 
@@ -76,6 +81,7 @@ impl<K, V> RbNode<K, V> {
                 value: MaybeUninit::uninit(),
                 key_value_moved: true,
                 rb_node_type: RbNodeType::Nil,
+                size: 0,
                 parent,
                 left: None,
                 right: None,
@@ -104,7 +110,50 @@ impl<K, V> RbNode<K, V> {
         unsafe { self.value.assume_init_ref() }
     }
 
-    pub fn init(&mut self, key: K, value: V, rb_node_type: RbNodeType) {
+    /// Returns references to the key and the value, which are not bound to the lifetime of this pointer.
+    ///
+    /// ## Safety
+    ///
+    /// The node must be non-Nil, and the references must not outlive its key and value.
+    pub unsafe fn key_value_ref<'a>(self) -> (&'a K, &'a V) {
+        let raw = self.0.as_ptr();
+
+        ((*raw).key.assume_init_ref(), (*raw).value.assume_init_ref())
+    }
+
+    /// Returns a reference to the key and a mutable reference to the value,
+    /// which are not bound to the lifetime of this pointer.
+    ///
+    /// ## Safety
+    ///
+    /// Same as `key_value_ref`, and no other reference to the value may exist while they're alive.
+    pub unsafe fn key_value_mut<'a>(self) -> (&'a K, &'a mut V) {
+        let raw = self.0.as_ptr();
+
+        ((*raw).key.assume_init_ref(), (*raw).value.assume_init_mut())
+    }
+
+    /// Returns a mutable reference to the key, which is not bound to the lifetime of this pointer.
+    ///
+    /// ## Safety
+    ///
+    /// The node must be non-Nil, no other reference to the key may exist while it's alive,
+    /// and the key must keep comparing equal.
+    pub unsafe fn key_mut<'a>(self) -> &'a mut K {
+        (*self.0.as_ptr()).key.assume_init_mut()
+    }
+
+    /// Returns a mutable reference to the value, which is not bound to the lifetime of this pointer.
+    ///
+    /// ## Safety
+    ///
+    /// Same as `key_value_mut`.
+    pub unsafe fn value_mut<'a>(self) -> &'a mut V {
+        (*self.0.as_ptr()).value.assume_init_mut()
+    }
+
+    /// Initialize Nil node with key, value, allocating its Nil children from `pool`.
+    pub fn init(&mut self, key: K, value: V, rb_node_type: RbNodeType, pool: &mut NodePool<K, V>) {
         if let RbNodeType::Nil = rb_node_type {
             return;
         }
@@ -113,13 +162,15 @@ impl<K, V> RbNode<K, V> {
         self.value.write(value);
         self.key_value_moved = false;
 
-        self.left = Some(RbNode::new(Some(*self)));
-        self.right = Some(RbNode::new(Some(*self)));
+        self.left = Some(pool.alloc(Some(*self)));
+        self.right = Some(pool.alloc(Some(*self)));
 
         self.rb_node_type = rb_node_type;
+        self.size = 1;
     }
 
-    pub fn uninit(&mut self) {
+    /// Make node Nil dropping its key, value, and free its Nil children to `pool`.
+    pub fn uninit(&mut self, pool: &mut NodePool<K, V>) {
         if !self.key_value_moved {
             unsafe {
                 self.key.assume_init_drop();
@@ -129,22 +180,19 @@ impl<K, V> RbNode<K, V> {
         }
 
         self.rb_node_type = RbNodeType::Nil;
+        self.size = 0;
 
-        if let Some(mut left) = self.left {
+        if let Some(left) = self.left {
             if left.is_nil() {
-                unsafe {
-                    drop(Box::from_raw(left.as_ptr()));
-                }
+                pool.free(left);
             } else {
                 panic!("Left child is not Nil");
             }
         }
 
-        if let Some(mut right) = self.right {
+        if let Some(right) = self.right {
             if right.is_nil() {
-                unsafe {
-                    drop(Box::from_raw(right.as_ptr()));
-                }
+                pool.free(right);
             } else {
                 panic!("Right child is not Nil");
             }
@@ -154,18 +202,20 @@ impl<K, V> RbNode<K, V> {
         self.right = None;
     }
 
+    /// Recalculate `size` from children.
+    pub fn update_size(&mut self) {
+        let left_size = self.left.map_or(0, |left| left.size);
+        let right_size = self.right.map_or(0, |right| right.size);
+
+        self.size = left_size + right_size + 1;
+    }
+
     pub fn is_nil(&self) -> bool {
-        match self.rb_node_type {
-            RbNodeType::Nil => true,
-            _ => false,
-        }
+        matches!(self.rb_node_type, RbNodeType::Nil)
     }
 
     pub fn is_black(&self) -> bool {
-        match self.rb_node_type {
-            RbNodeType::Red => false,
-            _ => true,
-        }
+        !matches!(self.rb_node_type, RbNodeType::Red)
     }
 
     pub fn is_red(&self) -> bool {
@@ -189,7 +239,7 @@ impl<K, V> RbNode<K, V> {
 
 impl<K, V> Debug for RbNode<K, V>
 where
-    K: PartialOrd + Debug,
+    K: Debug,
     V: Debug,
 {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -0,0 +1,83 @@
+use super::node::RbNode;
+
+/// Allocator of Nil nodes for a tree.
+///
+/// With the `pool` feature, freed nodes are kept in a free list and reused by later allocations.
+/// Otherwise it's zero-sized and allocates and frees every node directly.
+pub(super) struct NodePool<K, V> {
+    #[cfg(feature = "pool")]
+    free: Vec<RbNode<K, V>>,
+    #[cfg(not(feature = "pool"))]
+    _marker: std::marker::PhantomData<RbNode<K, V>>,
+}
+
+impl<K, V> NodePool<K, V> {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(feature = "pool")]
+            free: Vec::new(),
+            #[cfg(not(feature = "pool"))]
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns a new Nil node with `parent`, reusing a freed node if there's one.
+    pub fn alloc(&mut self, parent: Option<RbNode<K, V>>) -> RbNode<K, V> {
+        #[cfg(feature = "pool")]
+        if let Some(mut node) = self.free.pop() {
+            node.parent = parent;
+            return node;
+        }
+
+        RbNode::new(parent)
+    }
+
+    /// Frees a Nil node without children.
+    pub fn free(&mut self, mut node: RbNode<K, V>) {
+        #[cfg(feature = "pool")]
+        {
+            node.parent = None;
+            self.free.push(node);
+        }
+
+        #[cfg(not(feature = "pool"))]
+        unsafe {
+            drop(Box::from_raw(node.as_ptr()));
+        }
+    }
+
+    /// Allocates nodes into the free list until it has at least `count` nodes.
+    #[cfg(feature = "pool")]
+    pub fn reserve(&mut self, count: usize) {
+        self.free.reserve(count.saturating_sub(self.free.len()));
+
+        while self.free.len() < count {
+            self.free.push(RbNode::new(None));
+        }
+    }
+
+    /// Returns the number of nodes in the free list.
+    #[cfg(feature = "pool")]
+    pub fn len(&self) -> usize {
+        self.free.len()
+    }
+
+    /// Releases all nodes in the free list.
+    #[cfg(feature = "pool")]
+    pub fn clear(&mut self) {
+        for mut node in self.free.drain(..) {
+            unsafe {
+                drop(Box::from_raw(node.as_ptr()));
+            }
+        }
+
+        self.free.shrink_to_fit();
+    }
+}
+
+#[cfg(feature = "pool")]
+impl<K, V> Drop for NodePool<K, V> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
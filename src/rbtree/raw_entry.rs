@@ -0,0 +1,126 @@
+use std::{borrow::Borrow, cmp::Ordering};
+
+use super::{node::RbNode, Augment, NoAugment, OccupiedEntry, RbTree};
+
+/// A builder for computing where in a tree a key-value pair would be stored.
+///
+/// This is constructed from the `raw_entry_mut` method on `RbTree`.
+pub struct RawEntryBuilderMut<'a, K, V, A: Augment<K, V> = NoAugment> {
+    pub(super) tree: &'a mut RbTree<K, V, A>,
+}
+
+/// A view into a single entry in a tree found by a raw lookup, which may either be vacant or occupied.
+pub enum RawEntryMut<'a, K, V, A: Augment<K, V> = NoAugment> {
+    Vacant(RawVacantEntryMut<'a, K, V, A>),
+    Occupied(OccupiedEntry<'a, K, V, A>),
+}
+
+/// A view into a vacant entry in a tree found by a raw lookup.
+///
+/// Unlike `VacantEntry`, it doesn't own a key, so the key is given when inserting.
+pub struct RawVacantEntryMut<'a, K, V, A: Augment<K, V> = NoAugment> {
+    tree: &'a mut RbTree<K, V, A>,
+    node: RbNode<K, V>,
+}
+
+impl<'a, K, V, A: Augment<K, V>> RawEntryBuilderMut<'a, K, V, A> {
+    /// Looks up the entry of the key.
+    pub fn from_key<Q>(self, key: &Q) -> RawEntryMut<'a, K, V, A>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.from_key_with(|other| key.cmp(other.borrow()))
+    }
+
+    /// Looks up the entry with a comparator closure.
+    ///
+    /// `cmp` returns the ordering of the searched key relative to the given key,
+    /// and must be consistent with the key order of the tree.
+    pub fn from_key_with<F>(self, cmp: F) -> RawEntryMut<'a, K, V, A>
+    where
+        F: FnMut(&K) -> Ordering,
+    {
+        let node = self.tree.find_nearest_node_by(self.tree.root, cmp);
+
+        match node.is_nil() {
+            true => RawEntryMut::Vacant(RawVacantEntryMut {
+                tree: self.tree,
+                node,
+            }),
+            false => RawEntryMut::Occupied(OccupiedEntry {
+                tree: self.tree,
+                node,
+            }),
+        }
+    }
+}
+
+impl<'a, K, V> RawEntryMut<'a, K, V> {
+    /// Inserts `default_key` and `default_value` if the entry is vacant,
+    /// and returns references to the key and the value.
+    pub fn or_insert(self, default_key: K, default_value: V) -> (&'a K, &'a mut V) {
+        self.or_insert_with(|| (default_key, default_value))
+    }
+
+    /// Inserts the result of `default` if the entry is vacant, and returns references to the key and the value.
+    pub fn or_insert_with<F: FnOnce() -> (K, V)>(self, default: F) -> (&'a K, &'a mut V) {
+        match self {
+            RawEntryMut::Vacant(entry) => {
+                let (key, value) = default();
+                entry.insert(key, value)
+            }
+            RawEntryMut::Occupied(entry) => {
+                let node = entry.node;
+
+                unsafe { node.key_value_mut() }
+            }
+        }
+    }
+}
+
+impl<'a, K, V, A: Augment<K, V>> RawVacantEntryMut<'a, K, V, A> {
+    /// Inserts the key-value pair into this entry, and returns the occupied entry of it.
+    ///
+    /// Unlike `insert`, it's offered by augmented trees too.
+    /// `key` must compare equal under the comparator used for the lookup, or the key order of the tree breaks.
+    pub fn insert_entry(self, key: K, value: V) -> OccupiedEntry<'a, K, V, A> {
+        let node = self.node;
+
+        self.tree.insert_at(node, key, value);
+
+        // rotations only relink nodes, so `node` still holds the inserted pair.
+        OccupiedEntry {
+            tree: self.tree,
+            node,
+        }
+    }
+}
+
+impl<'a, K, V> RawVacantEntryMut<'a, K, V> {
+    /// Inserts the key-value pair into this entry, and returns references to the key and the value.
+    ///
+    /// `key` must compare equal under the comparator used for the lookup, or the key order of the tree breaks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rbtree::{RawEntryMut, RbTree};
+    ///
+    /// let mut tree = RbTree::new();
+    /// tree.insert(1, "a");
+    /// tree.insert(3, "c");
+    ///
+    /// if let RawEntryMut::Vacant(entry) = tree.raw_entry_mut().from_key(&2) {
+    ///     assert_eq!(entry.insert(2, "b"), (&2, &mut "b"));
+    /// }
+    ///
+    /// assert!(tree.iter().eq([(&1, &"a"), (&2, &"b"), (&3, &"c")]));
+    /// assert!(tree.is_correct_rb_tree());
+    /// ```
+    pub fn insert(self, key: K, value: V) -> (&'a K, &'a mut V) {
+        let node = self.insert_entry(key, value).node;
+
+        unsafe { node.key_value_mut() }
+    }
+}